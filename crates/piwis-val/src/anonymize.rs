@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use sha2::{Digest, Sha256};
+use crate::archive::ValArchive;
+use crate::identification::SERIAL_NUMBER_KEYS;
+use crate::{Measurement, RawContent, RawElement, VehicleAnalysisLog};
+
+// The first 11 VIN positions hold the manufacturer, vehicle descriptor, check
+// digit, model year and plant; only the remaining serial number is personal.
+const VIN_INFO_LEN: usize = 11;
+
+/// Personal and dealer data found in a log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PersonalField {
+    Vin,
+    Registration,
+    DealerName,
+    DealerCompany,
+    DealerAddress,
+    DealerZip,
+    DealerCity,
+    DealerTel,
+    DealerNo,
+    Order,
+    WarrantyNo,
+    /// Serial number of the diagnostic tester.
+    TesterSerial,
+    /// Serial numbers control units report in their identification.
+    EcuSerial,
+}
+
+const FIELD_NAMES: &[(PersonalField, &str)] = &[
+    (PersonalField::Vin, "vin"),
+    (PersonalField::Registration, "registration"),
+    (PersonalField::DealerName, "dealer-name"),
+    (PersonalField::DealerCompany, "dealer-company"),
+    (PersonalField::DealerAddress, "dealer-address"),
+    (PersonalField::DealerZip, "dealer-zip"),
+    (PersonalField::DealerCity, "dealer-city"),
+    (PersonalField::DealerTel, "dealer-tel"),
+    (PersonalField::DealerNo, "dealer-no"),
+    (PersonalField::Order, "order"),
+    (PersonalField::WarrantyNo, "warranty-no"),
+    (PersonalField::TesterSerial, "tester-serial"),
+    (PersonalField::EcuSerial, "ecu-serial"),
+];
+
+impl FromStr for PersonalField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FIELD_NAMES.iter()
+            .find(|(_, name)| *name == s)
+            .map(|(field, _)| *field)
+            .ok_or_else(|| format!("unknown field '{}', expected one of: {}", s,
+                                   FIELD_NAMES.iter().map(|(_, name)| *name).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+impl fmt::Display for PersonalField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, name) = FIELD_NAMES.iter().find(|(field, _)| field == self).unwrap();
+        f.write_str(name)
+    }
+}
+
+/// How a personal field is redacted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Redaction {
+    Keep,
+    /// Replaces every character with `X`, keeping the length.
+    #[default]
+    Mask,
+    /// Replaces the value with a SHA-256 of the salt and the value, truncated to
+    /// the value's length, so the same value hashes the same across logs
+    /// anonymized with the same salt. Short values keep few hash characters, e.g.
+    /// 6 for the serial number of a VIN, so anyone knowing the salt can find them
+    /// by hashing every candidate; the salt has to stay secret.
+    Hash,
+    /// Empties the value.
+    Drop,
+}
+
+impl FromStr for Redaction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Redaction::Keep),
+            "mask" => Ok(Redaction::Mask),
+            "hash" => Ok(Redaction::Hash),
+            "drop" => Ok(Redaction::Drop),
+            _ => Err(format!("unknown redaction '{}', expected one of: keep, mask, hash, drop", s)),
+        }
+    }
+}
+
+impl Redaction {
+    fn apply(self, value: &str, salt: &str) -> String {
+        match self {
+            Redaction::Keep => value.to_string(),
+            Redaction::Mask => "X".repeat(value.chars().count()),
+            Redaction::Hash => {
+                let digest = Sha256::new().chain_update(salt).chain_update(value).finalize();
+                digest.iter()
+                    .map(|b| format!("{:02X}", b))
+                    .collect::<String>()
+                    .chars()
+                    .take(value.chars().count())
+                    .collect()
+            }
+            Redaction::Drop => String::new(),
+        }
+    }
+}
+
+/// Controls [`VehicleAnalysisLog::anonymize`]. Fields without a rule get
+/// `redaction`.
+#[derive(Debug, Clone, Default)]
+pub struct AnonymizeOptions {
+    pub redaction: Redaction,
+    pub rules: HashMap<PersonalField, Redaction>,
+    /// Mixed into hashes so they cannot be reversed by hashing guessed values
+    /// without it. Should be random and kept secret, see [`Redaction::Hash`].
+    pub salt: String,
+    /// Redacts only the serial number part of the VIN, keeping the model, model
+    /// year and plant it encodes.
+    pub keep_vin_info: bool,
+}
+
+impl AnonymizeOptions {
+    pub fn redaction_for(&self, field: PersonalField) -> Redaction {
+        self.rules.get(&field).copied().unwrap_or(self.redaction)
+    }
+
+    fn redact(&self, field: PersonalField, value: &mut String) {
+        *value = self.redaction_for(field).apply(value, &self.salt);
+    }
+
+    fn redact_vin(&self, vin: &str) -> String {
+        match vin.get(..VIN_INFO_LEN) {
+            Some(info) if self.keep_vin_info => {
+                format!("{}{}", info, self.redaction_for(PersonalField::Vin).apply(&vin[VIN_INFO_LEN..], &self.salt))
+            }
+            _ => self.redaction_for(PersonalField::Vin).apply(vin, &self.salt),
+        }
+    }
+}
+
+// Personal values found in the header, each with its redacted form.
+struct Personal {
+    /// The VIN and registration, replaced wherever a control unit reports them.
+    vehicle: Vec<(String, String)>,
+    /// Every redacted field, replaced in raw content kept by lenient loads, which
+    /// nothing is known about.
+    all: Vec<(String, String)>,
+}
+
+impl Personal {
+    fn add(&mut self, field: PersonalField, old: String, new: &str) {
+        if old.is_empty() || old == new {
+            return;
+        }
+        if matches!(field, PersonalField::Vin | PersonalField::Registration) {
+            self.vehicle.push((old.clone(), new.to_string()));
+        }
+        self.all.push((old, new.to_string()));
+    }
+
+    // Longer values first, so that a value containing another is replaced whole.
+    fn sort(&mut self) {
+        self.vehicle.sort_by_key(|(old, _)| std::cmp::Reverse(old.len()));
+        self.all.sort_by_key(|(old, _)| std::cmp::Reverse(old.len()));
+    }
+}
+
+fn replace_all(value: &mut String, replacements: &[(String, String)]) {
+    for (old, new) in replacements {
+        if value.contains(old.as_str()) {
+            *value = value.replace(old.as_str(), new);
+        }
+    }
+}
+
+fn is_serial_number(key: &str) -> bool {
+    SERIAL_NUMBER_KEYS.contains(&key)
+}
+
+fn anonymize_raw(elements: &mut [RawElement], options: &AnonymizeOptions, personal: &Personal) {
+    for element in elements {
+        let is_serial = is_serial_number(element.name.trim_start_matches('@'));
+        match &mut element.content {
+            RawContent::Text(text) if is_serial => options.redact(PersonalField::EcuSerial, text),
+            RawContent::Text(text) => replace_all(text, &personal.all),
+            RawContent::Children(children) => anonymize_raw(children, options, personal),
+        }
+    }
+}
+
+fn anonymize_measurements(measurements: &mut [Measurement], options: &AnonymizeOptions, personal: &Personal) {
+    for measurement in measurements {
+        for value in measurement.get_values_mut().into_iter().flatten() {
+            let is_serial = is_serial_number(value.get_label()) || is_serial_number(value.get_text());
+            let Some(v) = value.get_value_mut() else {
+                continue;
+            };
+            if is_serial {
+                options.redact(PersonalField::EcuSerial, v);
+            } else {
+                replace_all(v, &personal.vehicle);
+            }
+        }
+        anonymize_raw(measurement.get_unknown_elements_mut(), options, personal);
+        if let Some(submeasurements) = measurement.get_submeasurements_mut() {
+            anonymize_measurements(submeasurements, options, personal);
+        }
+    }
+}
+
+impl VehicleAnalysisLog {
+    /// Redacts the VIN, registration, dealer record and serial numbers in place.
+    /// The VIN and registration are also replaced wherever a control unit reports
+    /// them, and every redacted value wherever raw content kept by a lenient load
+    /// holds it.
+    pub fn anonymize(&mut self, options: &AnonymizeOptions) {
+        let mut personal = Personal { vehicle: vec![], all: vec![] };
+        let mut redact = |field: PersonalField, value: &mut String| {
+            let old = value.clone();
+            options.redact(field, value);
+            personal.add(field, old, value);
+        };
+
+        let ident = &mut self.results_header.vehicle.ident;
+        let vin = std::mem::take(&mut ident.vin);
+        ident.vin = options.redact_vin(&vin);
+        redact(PersonalField::Registration, &mut ident.registration);
+
+        let dealer = &mut self.results_header.car_dealer;
+        redact(PersonalField::DealerName, &mut dealer.name);
+        redact(PersonalField::DealerCompany, &mut dealer.company);
+        redact(PersonalField::DealerAddress, &mut dealer.address);
+        redact(PersonalField::DealerZip, &mut dealer.zip);
+        redact(PersonalField::DealerCity, &mut dealer.city);
+        redact(PersonalField::DealerTel, &mut dealer.tel);
+        redact(PersonalField::DealerNo, &mut dealer.dealer_no);
+        redact(PersonalField::Order, &mut dealer.order);
+        redact(PersonalField::WarrantyNo, &mut dealer.warranty_no);
+
+        redact(PersonalField::TesterSerial, &mut self.result.header.equipment.serial_no);
+
+        personal.add(PersonalField::Vin, vin, &self.results_header.vehicle.ident.vin);
+        personal.sort();
+        for section in self.result.sections.iter_mut() {
+            anonymize_raw(section.get_unknown_elements_mut(), options, &personal);
+            anonymize_measurements(section.get_measurements_mut(), options, &personal);
+        }
+    }
+}
+
+impl ValArchive {
+    /// Anonymizes every protocol. Attachments are left as they are, they may hold
+    /// personal data this crate cannot redact.
+    pub fn anonymize(&mut self, options: &AnonymizeOptions) {
+        for protocol in self.protocols.iter_mut() {
+            protocol.log.anonymize(options);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_test_xml;
+
+
+    #[test]
+    fn test_anonymize() {
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml").replace(
+            r#"<VALUE FORMAT="ALPHA" TEXT="Serial number" LABEL="Seriennummer">00001234567890</VALUE>"#,
+            r#"<VALUE FORMAT="ALPHA" TEXT="Serial number" LABEL="Seriennummer">00001234567890</VALUE>
+        <VALUE FORMAT="ALPHA" TEXT="Vehicle identification number" LABEL="FIN">WP0ZZZ99ZTS392124</VALUE>"#);
+        let mut val = load_test_xml(&xml);
+        val.anonymize(&AnonymizeOptions {
+            rules: HashMap::from([(PersonalField::DealerCity, Redaction::Keep), (PersonalField::DealerTel, Redaction::Drop)]),
+            ..AnonymizeOptions::default()
+        });
+
+        assert_eq!(val.results_header.vehicle.ident.vin, "XXXXXXXXXXXXXXXXX");
+        assert_eq!(val.results_header.car_dealer.name, "XXXXXXXX");
+        assert_eq!(val.results_header.car_dealer.city, "Springfield");
+        assert_eq!(val.results_header.car_dealer.tel, "");
+        assert_eq!(val.result.header.equipment.serial_no, "XXXXXX");
+        let identification = val.get_section_by_title("Gateway (A7.1)").unwrap().get_identification().unwrap();
+        assert_eq!(identification.serial_number.unwrap(), "XXXXXXXXXXXXXX");
+        assert_eq!(identification.software_version.unwrap(), "0456");
+        assert!(!val.to_xml().unwrap().contains("392124"));
+    }
+
+    #[test]
+    fn test_anonymize_raw() {
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml")
+            .replace("<TITLE>Airbag (variant: A2.8)</TITLE>", "<TITLE>Airbag (variant: A2.8)</TITLE>\n      <NOTE>Customer Jane Doe, WP0ZZZ99ZTS392124</NOTE>")
+            .replacen(r#"<MEAS OBJECT="Fehler">"#, r#"<MEAS OBJECT="Fehler" Seriennummer="SN-778899">"#, 1)
+            .replacen(r#"LABEL="Priority">2</VALUE>"#, r#"LABEL="Priority">2</VALUE>
+          <VALUE FORMAT="ALPHA" TEXT="Note" LABEL="Notiz">ABC123 / WP0ZZZ99ZTS392124</VALUE>"#, 1);
+        let mut val = crate::LoadOptions::lenient().load_xml_reader(xml.as_bytes()).unwrap();
+        val.anonymize(&AnonymizeOptions::default());
+
+        let section = val.get_section_by_title("Airbag (variant: A2.8)").unwrap();
+        let text = RawElement { name: "$text".to_string(), content: RawContent::Text("Customer XXXXXXXX, XXXXXXXXXXXXXXXXX".to_string()) };
+        assert_eq!(section.get_unknown_elements()[0].content, RawContent::Children(vec![text]));
+        let fault = &section.get_measurements()[0];
+        assert_eq!(fault.get_unknown_elements()[0].content, RawContent::Text("XXXXXXXXX".to_string()));
+        let note = fault.get_submeasurements().unwrap()[0].get_value_by_label("Notiz").unwrap();
+        assert_eq!(note.get_value().unwrap(), "XXXXXX / XXXXXXXXXXXXXXXXX");
+
+        let written = val.to_xml().unwrap();
+        for personal in ["Jane Doe", "392124", "ABC123", "SN-778899"] {
+            assert!(!written.contains(personal), "{}", personal);
+        }
+    }
+
+    #[test]
+    fn test_anonymize_hash() {
+        let options = AnonymizeOptions {
+            redaction: Redaction::Hash,
+            salt: "workshop".to_string(),
+            keep_vin_info: true,
+            ..AnonymizeOptions::default()
+        };
+        let mut val1 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let mut val2 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_2.xml"));
+        val1.anonymize(&options);
+        val2.anonymize(&options);
+
+        let vin = &val1.results_header.vehicle.ident.vin;
+        assert_eq!(vin.len(), 17);
+        assert!(vin.starts_with("WP0ZZZ99ZTS"));
+        assert!(!vin.ends_with("392124"));
+        assert_eq!(vin, &val2.results_header.vehicle.ident.vin);
+        assert_ne!(val1.results_header.car_dealer.name, "Jane Doe");
+        assert_eq!(val1.results_header.car_dealer.name.len(), "Jane Doe".len());
+    }
+
+    #[test]
+    fn test_anonymize_archive() {
+        let mut archive = crate::LoadOptions::default().load_archive_path("tests/data").unwrap();
+        archive.anonymize(&AnonymizeOptions::default());
+        let zip = archive.to_zip_writer(std::io::Cursor::new(vec![])).unwrap().into_inner();
+
+        let written = ValArchive::from_zip_reader(std::io::Cursor::new(zip)).unwrap();
+        assert_eq!(written.protocols.len(), 2);
+        for protocol in &written.protocols {
+            assert!(!protocol.name.contains("392124"));
+            assert_eq!(protocol.log.results_header.vehicle.ident.vin, "XXXXXXXXXXXXXXXXX");
+        }
+
+        // logs of the same vehicle taken at the same time get the same name once masked
+        let log = || load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let mut archive = ValArchive {
+            protocols: vec![
+                crate::archive::ArchiveProtocol { name: "a.xml".to_string(), log: log() },
+                crate::archive::ArchiveProtocol { name: "b.xml".to_string(), log: log() },
+            ],
+            attachments: vec![],
+        };
+        archive.anonymize(&AnonymizeOptions::default());
+        let zip = archive.to_zip_writer(std::io::Cursor::new(vec![])).unwrap().into_inner();
+        let written = ValArchive::from_zip_reader(std::io::Cursor::new(zip)).unwrap();
+        let name = archive.protocols[0].log.file_name();
+        let names: Vec<_> = written.protocols.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec![format!("{}.xml", name), format!("{}_2.xml", name)]);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("dealer-no".parse(), Ok(PersonalField::DealerNo));
+        assert_eq!(PersonalField::TesterSerial.to_string(), "tester-serial");
+        assert!("dealer".parse::<PersonalField>().is_err());
+        assert_eq!("hash".parse(), Ok(Redaction::Hash));
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::Path;
+use std::str::FromStr;
+use crate::{find_files, is_xml_name, is_zip_file, Error, LoadOptions, Result, VehicleAnalysisLog, ZIP_SIGNATURE};
+
+/// Every FAP XML in a VAL archive is parsed into its own protocol; any other
+/// entry is kept as an attachment.
+#[derive(Debug)]
+pub struct ValArchive {
+    pub protocols: Vec<ArchiveProtocol>,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Debug)]
+pub struct ArchiveProtocol {
+    /// Name of the archive entry the protocol was read from.
+    pub name: String,
+    pub log: VehicleAnalysisLog,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Picks one protocol out of an archive, either by its position among the
+/// protocols or by the `PROTOKOLLTYPE` of its header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolSelector {
+    Index(usize),
+    Type(String),
+}
+
+impl FromStr for ProtocolSelector {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s.parse() {
+            Ok(index) => ProtocolSelector::Index(index),
+            Err(_) => ProtocolSelector::Type(s.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for ProtocolSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolSelector::Index(index) => write!(f, "#{}", index),
+            ProtocolSelector::Type(protocol_type) => write!(f, "of type '{}'", protocol_type),
+        }
+    }
+}
+
+/// FAP XMLs are told apart from other XML attachments by their results header.
+pub(crate) fn is_protocol(name: &str, data: &[u8]) -> bool {
+    is_xml_name(name) && find(data, b"<RESULTSHEADER").is_some()
+}
+
+fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+    data.windows(pattern.len()).position(|w| w == pattern)
+}
+
+// The `PROTOKOLLTYPE` of an unparsed FAP XML.
+fn sniff_protocol_type(data: &[u8]) -> Option<&str> {
+    let start = find(data, b"<PROTOKOLLTYPE>")? + b"<PROTOKOLLTYPE>".len();
+    let end = start + find(&data[start..], b"</PROTOKOLLTYPE>")?;
+    std::str::from_utf8(&data[start..end]).ok().map(str::trim)
+}
+
+impl ProtocolSelector {
+    /// Whether the protocol at `index` among the protocols of an archive, read
+    /// from `data` but not parsed yet, is the one picked.
+    pub(crate) fn picks(&self, index: usize, data: &[u8]) -> bool {
+        match self {
+            ProtocolSelector::Index(wanted) => index == *wanted,
+            ProtocolSelector::Type(protocol_type) => sniff_protocol_type(data).is_some_and(|t| t.eq_ignore_ascii_case(protocol_type)),
+        }
+    }
+}
+
+impl ValArchive {
+    // A bare FAP XML has no entry name, so it gets the one PIWIS would give it.
+    fn single(log: VehicleAnalysisLog) -> ValArchive {
+        let name = format!("{}.xml", log.file_name());
+        ValArchive { protocols: vec![ArchiveProtocol { name, log }], attachments: vec![] }
+    }
+
+    pub fn from_zip(filename: impl AsRef<Path>) -> Result<ValArchive> {
+        LoadOptions::default().load_archive(File::open(filename)?)
+    }
+
+    pub fn from_zip_reader<R: Read + Seek>(reader: R) -> Result<ValArchive> {
+        LoadOptions::default().load_archive(reader)
+    }
+
+    fn position(&self, selector: &ProtocolSelector) -> Option<usize> {
+        match selector {
+            ProtocolSelector::Index(index) => (*index < self.protocols.len()).then_some(*index),
+            ProtocolSelector::Type(protocol_type) => self.protocols.iter()
+                .position(|p| p.log.result.header.protocol_type.eq_ignore_ascii_case(protocol_type)),
+        }
+    }
+
+    pub fn select(&self, selector: &ProtocolSelector) -> Option<&ArchiveProtocol> {
+        self.position(selector).map(|index| &self.protocols[index])
+    }
+
+    /// Drops every protocol but the one picked by `selector`.
+    pub fn retain_protocol(&mut self, selector: &ProtocolSelector) -> Result<()> {
+        let index = self.position(selector).ok_or_else(|| Error::MissingProtocol(selector.clone()))?;
+        let protocol = self.protocols.swap_remove(index);
+        self.protocols = vec![protocol];
+        Ok(())
+    }
+
+    /// Takes the protocol picked by `selector`, or the first one if none is given.
+    pub fn into_protocol(mut self, selector: Option<&ProtocolSelector>) -> Result<VehicleAnalysisLog> {
+        match selector {
+            Some(selector) => match self.position(selector) {
+                Some(index) => Ok(self.protocols.swap_remove(index).log),
+                None => Err(Error::MissingProtocol(selector.clone())),
+            },
+            None if self.protocols.is_empty() => Err(Error::MissingXml),
+            None => Ok(self.protocols.swap_remove(0).log),
+        }
+    }
+
+    pub fn to_zip(&self, filename: impl AsRef<Path>) -> Result<()> {
+        self.to_zip_writer(File::create(filename)?)?;
+        Ok(())
+    }
+
+    /// Writes the archive as a VAL zip: the protocols, named after
+    /// [`VehicleAnalysisLog::file_name`] rather than the entries they were read
+    /// from, followed by the attachments. A name already taken, as by logs whose
+    /// VINs were masked alike, gets a `_2`, `_3`, ... suffix.
+    pub fn to_zip_writer<W: Write + Seek>(&self, writer: W) -> Result<W> {
+        let mut zip = zip::ZipWriter::new(writer);
+        let mut names: HashSet<String> = self.attachments.iter().map(|a| a.name.clone()).collect();
+        for protocol in &self.protocols {
+            let name = protocol.log.file_name();
+            let name = (1..).map(|n| match n {
+                    1 => format!("{}.xml", name),
+                    n => format!("{}_{}.xml", name, n),
+                })
+                .find(|name| !names.contains(name))
+                .unwrap();
+            names.insert(name.clone());
+            zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+            protocol.log.to_xml_writer(&mut zip)?;
+        }
+        for attachment in &self.attachments {
+            zip.start_file(attachment.name.as_str(), zip::write::SimpleFileOptions::default())?;
+            zip.write_all(&attachment.data)?;
+        }
+        Ok(zip.finish()?)
+    }
+}
+
+impl LoadOptions {
+    /// Loads the protocol picked by `protocol`, or the first one, out of files
+    /// named and read by `files`. Only that protocol is parsed.
+    pub(crate) fn load_protocol<I>(&self, files: I) -> Result<VehicleAnalysisLog>
+    where
+        I: IntoIterator<Item = Result<(String, Vec<u8>)>>,
+    {
+        let mut index = 0;
+        for file in files {
+            let (name, data) = file?;
+            if !is_protocol(&name, &data) {
+                continue;
+            }
+            if self.protocol.as_ref().is_none_or(|selector| selector.picks(index, &data)) {
+                return self.load_xml_reader(data.as_slice());
+            }
+            index += 1;
+        }
+        Err(match &self.protocol {
+            Some(selector) => Error::MissingProtocol(selector.clone()),
+            None => Error::MissingXml,
+        })
+    }
+
+    pub fn load_archive<R: Read + Seek>(&self, reader: R) -> Result<ValArchive> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let mut files = vec![];
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if file.is_dir() {
+                continue;
+            }
+            let mut data = vec![];
+            file.read_to_end(&mut data)?;
+            files.push((file.name().to_string(), data));
+        }
+        self.load_files(files)
+    }
+
+    /// Loads every protocol and attachment of a zip archive or of a directory
+    /// holding an extracted one, depending on what `path` points to. A bare FAP
+    /// XML is loaded as an archive holding just that protocol.
+    pub fn load_archive_path(&self, path: impl AsRef<Path>) -> Result<ValArchive> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let mut files = vec![];
+            for file in find_files(path)? {
+                let name = file.strip_prefix(path).unwrap_or(&file).to_string_lossy().replace('\\', "/");
+                files.push((name, std::fs::read(&file)?));
+            }
+            return self.load_files(files);
+        }
+        if is_zip_file(path)? {
+            return self.load_archive(File::open(path)?);
+        }
+        Ok(ValArchive::single(self.load_xml(path)?))
+    }
+
+    /// Loads every protocol and attachment of a zip archive held in memory, or a
+    /// bare FAP XML as an archive holding just that protocol.
+    pub fn load_archive_bytes(&self, bytes: &[u8]) -> Result<ValArchive> {
+        if bytes.starts_with(ZIP_SIGNATURE) {
+            self.load_archive(Cursor::new(bytes))
+        } else {
+            Ok(ValArchive::single(self.load_xml_reader(bytes)?))
+        }
+    }
+
+    fn load_files(&self, files: Vec<(String, Vec<u8>)>) -> Result<ValArchive> {
+        let mut protocols = vec![];
+        let mut attachments = vec![];
+        for (name, data) in files {
+            if is_protocol(&name, &data) {
+                protocols.push(ArchiveProtocol { name, log: self.load_xml_reader(data.as_slice())? });
+            } else {
+                attachments.push(Attachment { name, data });
+            }
+        }
+        Ok(ValArchive { protocols, attachments })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn test_archive() {
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        zip.start_file("report.pdf", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"%PDF-1.4").unwrap();
+        zip.start_file("FAP_SAMPLE_1.xml", SimpleFileOptions::default()).unwrap();
+        zip.write_all(include_bytes!("../tests/data/FAP_SAMPLE_1.xml")).unwrap();
+        zip.start_file("FAP_SAMPLE_2.xml", SimpleFileOptions::default()).unwrap();
+        zip.write_all(include_bytes!("../tests/data/FAP_SAMPLE_2.xml")).unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        let archive = ValArchive::from_zip_reader(Cursor::new(&zip)).unwrap();
+        let names: Vec<_> = archive.protocols.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["FAP_SAMPLE_1.xml", "FAP_SAMPLE_2.xml"]);
+        assert_eq!(archive.attachments, vec![Attachment { name: "report.pdf".to_string(), data: b"%PDF-1.4".to_vec() }]);
+
+        assert_eq!(archive.select(&"1".parse().unwrap()).unwrap().name, "FAP_SAMPLE_2.xml");
+        assert_eq!(archive.select(&"fap".parse().unwrap()).unwrap().name, "FAP_SAMPLE_1.xml");
+        assert!(archive.select(&"2".parse().unwrap()).is_none());
+
+        let options = |protocol: &str| LoadOptions { protocol: Some(protocol.parse().unwrap()), ..LoadOptions::default() };
+        let val = options("1").load_zip_reader(Cursor::new(&zip)).unwrap();
+        assert_eq!(val.results_header.vehicle.data.odometer.value, "12361");
+        let val = options("1").load_dir("tests/data").unwrap();
+        assert_eq!(val.results_header.vehicle.data.odometer.value, "12361");
+        assert!(matches!(options("DIAG").load_dir("tests/data"), Err(Error::MissingProtocol(_))));
+
+        let mut archive = LoadOptions::default().load_archive_path("tests/data").unwrap();
+        let names: Vec<_> = archive.protocols.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["FAP_SAMPLE_1.xml", "FAP_SAMPLE_2.xml"]);
+        let archive_xml = LoadOptions::default().load_archive_path("tests/data/FAP_SAMPLE_2.xml").unwrap();
+        assert_eq!(archive_xml.protocols[0].name, "FAP_WP0ZZZ99ZTS392124_20240804_154703_23.0.1.xml");
+
+        archive.retain_protocol(&"1".parse().unwrap()).unwrap();
+        archive.attachments.push(Attachment { name: "report.pdf".to_string(), data: b"%PDF-1.4".to_vec() });
+        let zip = archive.to_zip_writer(Cursor::new(vec![])).unwrap().into_inner();
+        let written = LoadOptions::default().load_archive_bytes(&zip).unwrap();
+        assert_eq!(written.protocols.len(), 1);
+        assert_eq!(written.protocols[0].name, archive_xml.protocols[0].name);
+        assert_eq!(written.attachments, archive.attachments);
+    }
+
+    #[test]
+    fn test_load_single_protocol() {
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        zip.start_file("FAP_SAMPLE_1.xml", SimpleFileOptions::default()).unwrap();
+        zip.write_all(include_bytes!("../tests/data/FAP_SAMPLE_1.xml")).unwrap();
+        zip.start_file("DIAG.xml", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"<FAP><RESULTSHEADER><PROTOKOLLTYPE>DIAG</PROTOKOLLTYPE>").unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        // the broken second protocol is only parsed when asked for
+        let val = LoadOptions::default().load_zip_reader(Cursor::new(&zip)).unwrap();
+        assert_eq!(val.result.header.protocol_type, "FAP");
+        let options = LoadOptions { protocol: Some("diag".parse().unwrap()), ..LoadOptions::default() };
+        assert!(matches!(options.load_zip_reader(Cursor::new(&zip)), Err(Error::Xml(_) | Error::Schema { .. })));
+        assert!(ValArchive::from_zip_reader(Cursor::new(&zip)).is_err());
+    }
+}
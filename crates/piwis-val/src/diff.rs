@@ -0,0 +1,825 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use serde::Serialize;
+use crate::faults::{ambiguous_faults, diff_faults, fault_code, FaultChange, FaultDiffEntry, FaultValueChange};
+use crate::header::{diff_header, HeaderChange, HeaderDiff};
+use crate::matching::{ambiguous_keys, pair_in_order, SectionMatching};
+use crate::quantity::{parse_number, Quantity, Unit};
+use crate::rules::{DiffRules, Severity};
+use crate::{Measurement, ValueEnum, VehicleAnalysisLog};
+
+/// How far two numeric values may drift apart before they are reported as
+/// changed. Either bound accepts a change.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tolerance {
+    pub absolute: f64,
+    /// Fraction of the old value, e.g. `0.02` for 2%, as in [`Drift::relative`].
+    pub relative: f64,
+}
+
+impl Tolerance {
+    pub fn accepts(&self, old: f64, new: f64) -> bool {
+        let delta = (new - old).abs();
+        delta <= self.absolute || delta <= self.relative * old.abs()
+    }
+}
+
+/// Parses `0.05` as an absolute tolerance, `2%` as a relative one and
+/// `0.05/2%` as both.
+impl FromStr for Tolerance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tolerance = Tolerance::default();
+        for part in s.split('/') {
+            let invalid = || format!("invalid tolerance '{}', expected e.g. 0.05, 2% or 0.05/2%", s);
+            match part.trim().strip_suffix('%') {
+                Some(percent) => tolerance.relative = parse_number(percent).ok_or_else(invalid)? / 100.0,
+                None => tolerance.absolute = parse_number(part).ok_or_else(invalid)?,
+            }
+        }
+        Ok(tolerance)
+    }
+}
+
+/// Tolerances for numeric values, looked up by the value's label, then its unit
+/// and then `default`. A unit's tolerance also applies to values in any unit
+/// converting to it, e.g. one for V to values in mV. Without one, any numeric
+/// change is reported.
+#[derive(Debug, Clone, Default)]
+pub struct Tolerances {
+    pub default: Option<Tolerance>,
+    pub units: HashMap<Unit, Tolerance>,
+    pub labels: HashMap<String, Tolerance>,
+}
+
+impl Tolerances {
+    /// Whether the change from `old` to `new`, both in the same unit, is within
+    /// the tolerance for `label`. A unit's tolerance is checked with both values
+    /// converted to that unit.
+    pub fn accepts(&self, label: &str, old: &Quantity, new: &Quantity) -> bool {
+        if let Some(tolerance) = self.labels.get(label) {
+            return tolerance.accepts(old.value, new.value);
+        }
+        let by_unit = old.unit.as_ref().and_then(|unit| self.units.get(unit)).map(|tolerance| (tolerance, old.value, new.value))
+            .or_else(|| self.units.iter().find_map(|(unit, tolerance)| {
+                Some((tolerance, old.to(unit.clone())?.value, new.to(unit.clone())?.value))
+            }));
+        match by_unit {
+            Some((tolerance, old, new)) => tolerance.accepts(old, new),
+            None => self.default.is_some_and(|tolerance| tolerance.accepts(old.value, new.value)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DiffConfig {
+    pub include_mistakes: bool,
+    pub include_values: bool,
+    pub include_identification: bool,
+    pub include_coding: bool,
+    pub include_extended_errors: bool,
+    pub include_header: bool,
+    pub tolerances: Tolerances,
+    pub rules: DiffRules,
+    pub section_matching: SectionMatching,
+}
+
+impl DiffConfig {
+    pub fn new(include_coding: bool, include_mistakes: bool, include_identification: bool, include_values: bool, include_extended_errors: bool) -> DiffConfig {
+        DiffConfig {
+            include_mistakes,
+            include_values,
+            include_identification,
+            include_coding,
+            include_extended_errors,
+            include_header: false,
+            tolerances: Tolerances::default(),
+            rules: DiffRules::default(),
+            section_matching: SectionMatching::default(),
+        }
+    }
+
+    fn should_compare(&self, m: &Measurement) -> bool {
+        match m {
+            Measurement::Codierung(_) => self.include_coding,
+            Measurement::Identifikation(_) => self.include_identification,
+            // faults are matched by their DTC number in `faults::diff_faults`,
+            // those without one are compared like any other measurement
+            Measurement::Fehler(fault) => self.include_mistakes && fault_code(fault).is_none(),
+            Measurement::Messwerte(_) => self.include_values,
+            Measurement::ErweiterterFehlerspeicher(_) => self.include_extended_errors,
+            Measurement::Unknown(_) => false,
+        }
+    }
+}
+
+/// Location of a difference: the section title followed by the titles of the
+/// measurement and any nested sub-measurements.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiffPath {
+    pub section: String,
+    pub measurements: Vec<String>,
+}
+
+/// The element a difference refers to.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum DiffItem {
+    Section,
+    Measurement,
+    Submeasurements,
+    Values,
+    Value(ValueEnum),
+}
+
+impl DiffItem {
+    pub fn get_kind(&self) -> ItemKind {
+        match self {
+            DiffItem::Section => ItemKind::Section,
+            DiffItem::Measurement => ItemKind::Measurement,
+            DiffItem::Submeasurements => ItemKind::Submeasurements,
+            DiffItem::Values => ItemKind::Values,
+            DiffItem::Value(_) => ItemKind::Value,
+        }
+    }
+
+    pub fn get_value(&self) -> Option<&ValueEnum> {
+        match self {
+            DiffItem::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiffEntry {
+    /// Present in the second log only.
+    Added { path: DiffPath, item: DiffItem },
+    /// Present in the first log only.
+    Removed { path: DiffPath, item: DiffItem },
+    /// Value present in both logs with different contents.
+    Changed { path: DiffPath, old: ValueEnum, new: ValueEnum },
+}
+
+impl DiffEntry {
+    pub fn get_path(&self) -> &DiffPath {
+        match self {
+            DiffEntry::Added { path, .. } => path,
+            DiffEntry::Removed { path, .. } => path,
+            DiffEntry::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// A numeric value that differs between both logs. `new` is converted to the
+/// unit of `old`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Drift {
+    pub path: DiffPath,
+    pub label: String,
+    pub text: String,
+    pub old: Quantity,
+    pub new: Quantity,
+    /// Set when the change is within the value's tolerance and therefore not
+    /// reported in `ValDiff::entries`.
+    pub within_tolerance: bool,
+}
+
+impl Drift {
+    pub fn delta(&self) -> f64 {
+        self.new.value - self.old.value
+    }
+
+    /// `delta` relative to the old value, `None` if that is zero.
+    pub fn relative(&self) -> Option<f64> {
+        (self.old.value != 0.0).then(|| self.delta() / self.old.value.abs())
+    }
+}
+
+/// Drift magnitudes of all values sharing a unit.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DriftSummary {
+    pub unit: Option<Unit>,
+    pub count: usize,
+    pub within_tolerance: usize,
+    pub max_delta: f64,
+    pub mean_delta: f64,
+    pub max_relative: Option<f64>,
+}
+
+/// All differences between two logs, in the order they were found. Fault memory
+/// differences are kept apart in `faults` when `include_mistakes` is set, header
+/// differences in `header` when `include_header` is set. Numeric values that
+/// differ are also listed in `drift`, including the ones within tolerance.
+/// Sections paired despite different titles are listed in `renamed_sections`;
+/// their entries carry the title of the first log. Duplicate titles or labels
+/// that could not be paired reliably are listed in `ambiguous`.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ValDiff {
+    pub entries: Vec<DiffEntry>,
+    pub faults: Vec<FaultDiffEntry>,
+    pub header: HeaderDiff,
+    pub drift: Vec<Drift>,
+    pub renamed_sections: Vec<SectionRename>,
+    pub ambiguous: Vec<AmbiguousMatch>,
+}
+
+/// A section paired with a differently titled section of the second log.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SectionRename {
+    pub old: String,
+    pub new: String,
+}
+
+/// A measurement title, value label or DTC number found several times under
+/// `path` in either log. The occurrences were paired in document order, which
+/// may pair the wrong ones. For [`ItemKind::Section`] the
+/// key, also used as section of `path`, is one several sections share, which were
+/// left unpaired by the fuzzy section matching.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AmbiguousMatch {
+    pub path: DiffPath,
+    /// [`ItemKind::Section`], [`ItemKind::Measurement`], [`ItemKind::Value`] or
+    /// [`ItemKind::Fault`].
+    pub item: ItemKind,
+    pub key: String,
+    pub count1: usize,
+    pub count2: usize,
+}
+
+impl ValDiff {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.header.is_empty() && self.faults.iter().all(|f| matches!(&f.change, FaultChange::Persisting { changes } if changes.is_empty()))
+    }
+
+    pub fn records(&self) -> Vec<DiffRecord> {
+        let mut records: Vec<_> = self.header.changes.iter().map(DiffRecord::from).collect();
+        records.extend(self.entries.iter().map(DiffRecord::from));
+        records.extend(self.faults.iter().flat_map(DiffRecord::from_fault));
+        records.extend(self.ambiguous.iter().map(DiffRecord::from));
+        records
+    }
+
+    /// Absolute drift per unit, in the order the units were first found.
+    pub fn drift_summary(&self) -> Vec<DriftSummary> {
+        let mut summaries: Vec<DriftSummary> = vec![];
+        for drift in &self.drift {
+            let index = match summaries.iter().position(|s| s.unit == drift.old.unit) {
+                Some(index) => index,
+                None => {
+                    summaries.push(DriftSummary {
+                        unit: drift.old.unit.clone(),
+                        count: 0,
+                        within_tolerance: 0,
+                        max_delta: 0.0,
+                        mean_delta: 0.0,
+                        max_relative: None,
+                    });
+                    summaries.len() - 1
+                }
+            };
+            let summary = &mut summaries[index];
+            let delta = drift.delta().abs();
+            summary.count += 1;
+            summary.within_tolerance += drift.within_tolerance as usize;
+            summary.max_delta = summary.max_delta.max(delta);
+            // running sum, divided below
+            summary.mean_delta += delta;
+            if let Some(relative) = drift.relative() {
+                summary.max_relative = Some(summary.max_relative.unwrap_or(0.0).max(relative.abs()));
+            }
+        }
+        for summary in &mut summaries {
+            summary.mean_delta /= summary.count as f64;
+        }
+        summaries
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+    /// See [`AmbiguousMatch`].
+    Ambiguous,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    Section,
+    Measurement,
+    Submeasurements,
+    Values,
+    Value,
+    Fault,
+    Header,
+}
+
+/// Flat, machine-readable view of a [`DiffEntry`] with one field per column.
+/// `label`, `text` and `unit` are only set for value differences. For faults the
+/// first element of `measurements` is the DTC number; header changes carry the
+/// field name in `label`. An [`AmbiguousMatch`] has its key where the item's
+/// title or label would be, and both counts in `old` and `new`. `severity` is
+/// set by [`DiffRules::tag`].
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiffRecord {
+    pub kind: ChangeKind,
+    pub item: ItemKind,
+    pub section: String,
+    pub measurements: Vec<String>,
+    pub label: Option<String>,
+    pub text: Option<String>,
+    pub unit: Option<String>,
+    pub old: Option<String>,
+    pub new: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+}
+
+impl DiffRecord {
+    fn fault(fault: &FaultDiffEntry, kind: ChangeKind, measurements: Vec<String>, old: Option<&ValueEnum>, new: Option<&ValueEnum>) -> DiffRecord {
+        DiffRecord {
+            kind,
+            item: ItemKind::Fault,
+            section: fault.section.clone(),
+            measurements,
+            label: old.or(new).map(|v| v.get_label().clone()),
+            text: old.or(new).map(|v| v.get_text().clone()).or(fault.description.clone()),
+            unit: old.or(new).and_then(|v| v.get_unit().cloned()),
+            old: old.and_then(|v| v.get_value().cloned()),
+            new: new.and_then(|v| v.get_value().cloned()),
+            severity: None,
+        }
+    }
+
+    /// One record for an appeared or cleared fault, one per changed value of a
+    /// persisting fault.
+    pub fn from_fault(fault: &FaultDiffEntry) -> Vec<DiffRecord> {
+        match &fault.change {
+            FaultChange::Appeared => vec![DiffRecord::fault(fault, ChangeKind::Added, vec![fault.code.clone()], None, None)],
+            FaultChange::Cleared => vec![DiffRecord::fault(fault, ChangeKind::Removed, vec![fault.code.clone()], None, None)],
+            FaultChange::Persisting { changes } => changes.iter()
+                .map(|change| DiffRecord::from_fault_change(fault, change))
+                .collect(),
+        }
+    }
+
+    pub fn from_fault_change(fault: &FaultDiffEntry, change: &FaultValueChange) -> DiffRecord {
+        let mut measurements = vec![fault.code.clone()];
+        measurements.extend(change.measurements.iter().cloned());
+        DiffRecord::fault(fault, ChangeKind::Changed, measurements, change.old.as_ref(), change.new.as_ref())
+    }
+}
+
+impl From<&HeaderChange> for DiffRecord {
+    fn from(change: &HeaderChange) -> Self {
+        DiffRecord {
+            kind: ChangeKind::Changed,
+            item: ItemKind::Header,
+            section: String::new(),
+            measurements: vec![],
+            label: Some(change.field.to_string()),
+            text: None,
+            unit: None,
+            old: Some(change.old.clone()),
+            new: Some(change.new.clone()),
+            severity: None,
+        }
+    }
+}
+
+impl From<&AmbiguousMatch> for DiffRecord {
+    fn from(ambiguous: &AmbiguousMatch) -> Self {
+        let mut measurements = ambiguous.path.measurements.clone();
+        if matches!(ambiguous.item, ItemKind::Measurement | ItemKind::Fault) {
+            measurements.push(ambiguous.key.clone());
+        }
+        DiffRecord {
+            kind: ChangeKind::Ambiguous,
+            item: ambiguous.item,
+            section: ambiguous.path.section.clone(),
+            measurements,
+            label: (ambiguous.item == ItemKind::Value).then(|| ambiguous.key.clone()),
+            text: None,
+            unit: None,
+            old: Some(ambiguous.count1.to_string()),
+            new: Some(ambiguous.count2.to_string()),
+            severity: None,
+        }
+    }
+}
+
+impl From<&DiffEntry> for DiffRecord {
+    fn from(entry: &DiffEntry) -> Self {
+        let path = entry.get_path();
+        let (kind, item, old, new) = match entry {
+            DiffEntry::Added { item, .. } => (ChangeKind::Added, item.get_kind(), None, item.get_value()),
+            DiffEntry::Removed { item, .. } => (ChangeKind::Removed, item.get_kind(), item.get_value(), None),
+            DiffEntry::Changed { old, new, .. } => (ChangeKind::Changed, ItemKind::Value, Some(old), Some(new)),
+        };
+        DiffRecord {
+            kind,
+            item,
+            section: path.section.clone(),
+            measurements: path.measurements.clone(),
+            label: old.or(new).map(|v| v.get_label().clone()),
+            text: old.or(new).map(|v| v.get_text().clone()),
+            unit: old.or(new).and_then(|v| v.get_unit().cloned()),
+            old: old.and_then(|v| v.get_value().cloned()),
+            new: new.and_then(|v| v.get_value().cloned()),
+            severity: None,
+        }
+    }
+}
+
+/// Compares `val1` against `val2`. Sections are paired by `section_matching`,
+/// measurements and sub-measurements by title and values by label and text,
+/// then by label alone; items found in only one of the logs are reported as
+/// removed (first log only) or added (second log only). Repeated titles and
+/// labels are paired in document order, the n-th occurrence in `val1` with the
+/// n-th in `val2`, and reported in [`ValDiff::ambiguous`], as are repeated DTC
+/// numbers.
+pub fn diff(val1: &VehicleAnalysisLog, val2: &VehicleAnalysisLog, config: &DiffConfig) -> ValDiff {
+    let mut differ = Differ { config, entries: vec![], drift: vec![], ambiguous: vec![] };
+    let mut faults = vec![];
+    let mut renamed_sections = vec![];
+    let (pairs, ambiguous_sections) = config.section_matching.pair_reporting(val1, val2);
+    differ.ambiguous.extend(ambiguous_sections.into_iter().map(|ambiguous| AmbiguousMatch {
+        path: DiffPath { section: ambiguous.key.clone(), measurements: vec![] },
+        item: ItemKind::Section,
+        key: ambiguous.key,
+        count1: ambiguous.count1,
+        count2: ambiguous.count2,
+    }));
+    for pair in pairs {
+        let (section, other_section) = match pair {
+            (Some(section), Some(other_section)) => (section, other_section),
+            (Some(section), None) => {
+                let path = DiffPath { section: section.get_title().clone(), measurements: vec![] };
+                differ.entries.push(DiffEntry::Removed { path, item: DiffItem::Section });
+                continue;
+            }
+            (None, Some(other_section)) => {
+                let path = DiffPath { section: other_section.get_title().clone(), measurements: vec![] };
+                differ.entries.push(DiffEntry::Added { path, item: DiffItem::Section });
+                continue;
+            }
+            (None, None) => continue,
+        };
+        if section.get_title() != other_section.get_title() {
+            renamed_sections.push(SectionRename { old: section.get_title().clone(), new: other_section.get_title().clone() });
+        }
+        let mut path = DiffPath { section: section.get_title().clone(), measurements: vec![] };
+        differ.measurements(&mut path, section.get_measurements(), other_section.get_measurements());
+        if config.include_mistakes {
+            differ.ambiguous.extend(ambiguous_faults(section, other_section).into_iter().map(|(key, count1, count2)| {
+                AmbiguousMatch { path: path.clone(), item: ItemKind::Fault, key, count1, count2 }
+            }));
+            faults.extend(diff_faults(section, other_section));
+        }
+    }
+    // logs of different vehicles are flagged even without the header's changes
+    let header = if config.include_header {
+        diff_header(val1, val2)
+    } else {
+        HeaderDiff { vin_mismatch: val1.results_header.vehicle.ident.vin != val2.results_header.vehicle.ident.vin, ..HeaderDiff::default() }
+    };
+    let mut val_diff = ValDiff { entries: differ.entries, faults, header, drift: differ.drift, renamed_sections, ambiguous: differ.ambiguous };
+    config.rules.retain(&mut val_diff);
+    val_diff
+}
+
+// The texts of the values labelled `label`, sorted.
+fn texts_of<'a>(values: &'a [ValueEnum], label: &str) -> Vec<&'a String> {
+    let mut texts: Vec<_> = values.iter().filter(|v| v.get_label() == label).map(|v| v.get_text()).collect();
+    texts.sort();
+    texts
+}
+
+// Walks both logs side by side, pairing the measurements and values of each
+// level before descending into them. The walker in `crate::walk` traverses a
+// single log, so it is not used here.
+struct Differ<'a> {
+    config: &'a DiffConfig,
+    entries: Vec<DiffEntry>,
+    drift: Vec<Drift>,
+    ambiguous: Vec<AmbiguousMatch>,
+}
+
+impl Differ<'_> {
+    fn measurements(&mut self, path: &mut DiffPath, measurements: &[Measurement], other_measurements: &[Measurement]) {
+        let measurements: Vec<_> = measurements.iter().filter(|m| self.config.should_compare(m)).collect();
+        let other_measurements: Vec<_> = other_measurements.iter().filter(|m| self.config.should_compare(m)).collect();
+        self.ambiguous(path, ItemKind::Measurement, ambiguous_keys(&measurements, &other_measurements, |m| m.get_title()));
+        let (pairs, taken) = pair_in_order(&measurements, &other_measurements, &[&|a, b| a.get_title() == b.get_title()]);
+
+        for (measurement, other) in measurements.iter().zip(pairs) {
+            path.measurements.push(measurement.get_title().clone());
+            let Some(other_measurement) = other.map(|j| other_measurements[j]) else {
+                self.entries.push(DiffEntry::Removed { path: path.clone(), item: DiffItem::Measurement });
+                path.measurements.pop();
+                continue;
+            };
+
+            match (measurement.get_submeasurements(), other_measurement.get_submeasurements()) {
+                (Some(nested_measurements), Some(other_nested_measurements)) =>
+                    self.measurements(path, nested_measurements, other_nested_measurements),
+                (Some(_), None) =>
+                    self.entries.push(DiffEntry::Removed { path: path.clone(), item: DiffItem::Submeasurements }),
+                (None, Some(_)) =>
+                    self.entries.push(DiffEntry::Added { path: path.clone(), item: DiffItem::Submeasurements }),
+                _ => (),
+            }
+
+            self.values(path, measurement.get_values(), other_measurement.get_values());
+            path.measurements.pop();
+        }
+
+        for (other_measurement, _) in other_measurements.iter().zip(taken).filter(|(_, taken)| !taken) {
+            path.measurements.push(other_measurement.get_title().clone());
+            self.entries.push(DiffEntry::Added { path: path.clone(), item: DiffItem::Measurement });
+            path.measurements.pop();
+        }
+    }
+
+    fn values(&mut self, path: &DiffPath, values: Option<&Vec<ValueEnum>>, other_values: Option<&Vec<ValueEnum>>) {
+        match (values, other_values) {
+            (Some(values), Some(other_values)) => {
+                // a repeated label is not ambiguous when its texts pair every value up
+                let mut keys = ambiguous_keys(values, other_values, |v| v.get_label());
+                keys.retain(|(label, _, _)| {
+                    let texts1 = texts_of(values, label);
+                    texts1 != texts_of(other_values, label) || texts1.windows(2).any(|w| w[0] == w[1])
+                });
+                self.ambiguous(path, ItemKind::Value, keys);
+                let (pairs, taken) = pair_in_order(values, other_values, &[
+                    &|a, b| a.get_label() == b.get_label() && a.get_text() == b.get_text(),
+                    &|a, b| a.get_label() == b.get_label(),
+                ]);
+                for (value, other) in values.iter().zip(pairs) {
+                    match other {
+                        Some(j) => self.value(path, value, &other_values[j]),
+                        None => self.entries.push(DiffEntry::Removed { path: path.clone(), item: DiffItem::Value(value.clone()) }),
+                    }
+                }
+                for (other_value, _) in other_values.iter().zip(taken).filter(|(_, taken)| !taken) {
+                    self.entries.push(DiffEntry::Added { path: path.clone(), item: DiffItem::Value(other_value.clone()) });
+                }
+            }
+            (Some(_), None) => self.entries.push(DiffEntry::Removed { path: path.clone(), item: DiffItem::Values }),
+            (None, Some(_)) => self.entries.push(DiffEntry::Added { path: path.clone(), item: DiffItem::Values }),
+            _ => (),
+        }
+    }
+
+    fn ambiguous(&mut self, path: &DiffPath, item: ItemKind, keys: Vec<(&str, usize, usize)>) {
+        self.ambiguous.extend(keys.into_iter().map(|(key, count1, count2)| {
+            AmbiguousMatch { path: path.clone(), item, key: key.to_string(), count1, count2 }
+        }));
+    }
+
+    // Numeric values are compared as numbers in the same unit, so `1,0` equals
+    // `1.0`, `12400 mV` equals `12,4 V` and `12,4 V` differs from `12,4 mV`.
+    // Values in units that do not convert are changed even with the same text.
+    fn value(&mut self, path: &DiffPath, value: &ValueEnum, other_value: &ValueEnum) {
+        let (old, new) = (value.get_quantity(), other_value.get_quantity());
+        if (old.is_none() || new.is_none()) && value.get_value() == other_value.get_value() {
+            return;
+        }
+        let numbers = old.and_then(|old| Some((new?.to_unit_of(&old)?, old)));
+        if let Some((new, old)) = numbers {
+            if new.value == old.value {
+                return;
+            }
+            let within_tolerance = self.config.tolerances.accepts(value.get_label(), &old, &new);
+            self.drift.push(Drift {
+                path: path.clone(),
+                label: value.get_label().clone(),
+                text: value.get_text().clone(),
+                old,
+                new,
+                within_tolerance,
+            });
+            if within_tolerance {
+                return;
+            }
+        }
+        self.entries.push(DiffEntry::Changed { path: path.clone(), old: value.clone(), new: other_value.clone() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_test_xml;
+    use crate::AlphaValue;
+
+
+    #[test]
+    fn test_diff() {
+        let val1 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let val2 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_2.xml"));
+
+        let changes = diff(&val1, &val2, &DiffConfig::new(true, false, false, false, false));
+        assert_eq!(changes.entries, vec![
+            DiffEntry::Changed {
+                path: DiffPath {
+                    section: "Gateway (A7.1)".to_string(),
+                    measurements: vec!["Control unit, coding".to_string()],
+                },
+                old: ValueEnum::Alpha(AlphaValue {
+                    text: "Kodierwert: Bitfield (2) comfort_operation".to_string(),
+                    label: "Kodierwert.comfort_operation".to_string(),
+                    value: Some("active".to_string()),
+                }),
+                new: ValueEnum::Alpha(AlphaValue {
+                    text: "Kodierwert: Bitfield (2) comfort_operation".to_string(),
+                    label: "Kodierwert.comfort_operation".to_string(),
+                    value: Some("not_active".to_string()),
+                }),
+            },
+            DiffEntry::Added {
+                path: DiffPath {
+                    section: "Gateway (A7.1)".to_string(),
+                    measurements: vec!["Control unit, coding".to_string()],
+                },
+                item: DiffItem::Value(ValueEnum::Alpha(AlphaValue {
+                    text: "Kodierwert: Bitfield (2) horn_acknowledgment".to_string(),
+                    label: "Kodierwert.horn_acknowledgment".to_string(),
+                    value: Some("active".to_string()),
+                })),
+            },
+            DiffEntry::Removed {
+                path: DiffPath { section: "Rear-view camera".to_string(), measurements: vec![] },
+                item: DiffItem::Section,
+            },
+            DiffEntry::Added {
+                path: DiffPath { section: "Parking assistant".to_string(), measurements: vec![] },
+                item: DiffItem::Section,
+            },
+        ]);
+
+        assert!(diff(&val1, &val1, &DiffConfig::new(true, true, true, true, true)).is_empty());
+    }
+
+    #[test]
+    fn test_records() {
+        let val1 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let val2 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_2.xml"));
+
+        let records = diff(&val1, &val2, &DiffConfig::new(false, false, false, true, false)).records();
+        assert_eq!(records, vec![
+            DiffRecord {
+                kind: ChangeKind::Changed,
+                item: ItemKind::Value,
+                section: "Gateway (A7.1)".to_string(),
+                measurements: vec!["Measured values".to_string()],
+                label: Some("Spannung_Klemme_30".to_string()),
+                text: Some("Terminal 30 voltage".to_string()),
+                unit: Some("V".to_string()),
+                old: Some("12,41".to_string()),
+                new: Some("12,43".to_string()),
+                severity: None,
+            },
+            DiffRecord {
+                kind: ChangeKind::Removed,
+                item: ItemKind::Section,
+                section: "Rear-view camera".to_string(),
+                measurements: vec![],
+                label: None,
+                text: None,
+                unit: None,
+                old: None,
+                new: None,
+                severity: None,
+            },
+            DiffRecord {
+                kind: ChangeKind::Added,
+                item: ItemKind::Section,
+                section: "Parking assistant".to_string(),
+                measurements: vec![],
+                label: None,
+                text: None,
+                unit: None,
+                old: None,
+                new: None,
+                severity: None,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_tolerance() {
+        let val1 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let val2 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_2.xml"));
+        let is_voltage = |e: &DiffEntry| matches!(e, DiffEntry::Changed { old, .. } if old.get_label() == "Spannung_Klemme_30");
+
+        let mut config = DiffConfig::new(false, false, false, true, false);
+        config.tolerances.units.insert(Unit::Volt, "0.05".parse().unwrap());
+        let changes = diff(&val1, &val2, &config);
+        assert!(!changes.entries.iter().any(is_voltage));
+        assert_eq!(changes.drift.len(), 1);
+        assert!(changes.drift[0].within_tolerance);
+        assert!((changes.drift[0].delta() - 0.02).abs() < 1e-9);
+        let summary = changes.drift_summary();
+        assert_eq!((summary[0].unit.as_ref(), summary[0].count, summary[0].within_tolerance), (Some(&Unit::Volt), 1, 1));
+
+        config.tolerances.labels.insert("Spannung_Klemme_30".to_string(), "0.1%".parse().unwrap());
+        let changes = diff(&val1, &val2, &config);
+        assert!(changes.entries.iter().any(is_voltage));
+        assert!(!changes.drift[0].within_tolerance);
+
+        // the same reading in another notation and unit is no change
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml")
+            .replace(r#"UNIT="V">12,41"#, r#"UNIT="mV">12410.0"#);
+        let changes = diff(&val1, &load_test_xml(&xml), &DiffConfig::new(false, false, false, true, false));
+        assert!(!changes.entries.iter().any(is_voltage));
+        assert!(changes.drift.is_empty());
+
+        // the same text in another unit is a change
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml").replace(r#"UNIT="V">12,41"#, r#"UNIT="mV">12,41"#);
+        let changes = diff(&val1, &load_test_xml(&xml), &DiffConfig::new(false, false, false, true, false));
+        assert!(changes.entries.iter().any(is_voltage));
+        assert_eq!(changes.drift.len(), 1);
+
+        // a tolerance in V applies to readings in mV, converted to V
+        let xml = include_str!("../tests/data/FAP_SAMPLE_2.xml").replace(r#"UNIT="V">12,43"#, r#"UNIT="mV">12430"#);
+        let mut config = DiffConfig::new(false, false, false, true, false);
+        config.tolerances.units.insert(Unit::Volt, "0.05".parse().unwrap());
+        let changes = diff(&load_test_xml(&xml), &val1, &config);
+        assert!(!changes.entries.iter().any(is_voltage));
+        assert!(changes.drift[0].within_tolerance);
+
+        // relative tolerances and drift share the old value as base
+        let tolerance: Tolerance = "2%".parse().unwrap();
+        assert!(tolerance.accepts(100.0, 102.0));
+        assert!(!tolerance.accepts(100.0, 102.03));
+
+        assert_eq!("0,05/2%".parse(), Ok(Tolerance { absolute: 0.05, relative: 0.02 }));
+        assert!("2 %%".parse::<Tolerance>().is_err());
+    }
+
+    #[test]
+    fn test_faults_without_code() {
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml")
+            .replace(r#"<VALUE FORMAT="ALPHA" TEXT="Fault code" LABEL="Fehlernummer">B100100</VALUE>"#, "")
+            .replace(r#"<VALUE FORMAT="ALPHA" TEXT="Fault text" LABEL="Fehlertext">Driver airbag, resistance too high</VALUE>"#, "");
+        let val1 = load_test_xml(&xml);
+        let val2 = load_test_xml(&xml.replace(r#"LABEL="Priority">1<"#, r#"LABEL="Priority">3<"#));
+
+        let changes = diff(&val1, &val2, &DiffConfig::new(false, true, false, false, true));
+        assert!(changes.faults.iter().all(|f| f.code == "B200000"));
+        assert_eq!(codes(&changes.entries), vec![(Some("1".to_string()), Some("3".to_string()))]);
+        assert_eq!(changes.entries[0].get_path().measurements, vec!["Fault", "erweiterter Fehlerspeicher"]);
+    }
+
+    fn codes(entries: &[DiffEntry]) -> Vec<(Option<String>, Option<String>)> {
+        entries.iter().map(DiffRecord::from).map(|r| (r.old, r.new)).collect()
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml");
+        let line = r#"<VALUE FORMAT="ALPHA" TEXT="Battery change: Scanner code" LABEL="Batteriewechsel_Technologie_zwei.Scannercode">205 BA24H9F0EGE</VALUE>"#;
+        let with_codes = |codes: &[(&str, &str)]| {
+            let lines: Vec<_> = codes.iter()
+                .map(|(text, code)| line.replace("Battery change: Scanner code", text).replace("205 BA24H9F0EGE", code))
+                .collect();
+            load_test_xml(&xml.replace(line, &lines.join("\n")))
+        };
+        let some = |code: &str| Some(code.to_string());
+        let config = DiffConfig::new(true, false, false, false, false);
+
+        // repeated labels are told apart by their text, wherever they are
+        let val1 = with_codes(&[("Scanner code", "A"), ("Scanner code, spare", "B")]);
+        let val2 = with_codes(&[("Scanner code, spare", "B"), ("Scanner code", "A")]);
+        let changes = diff(&val1, &val2, &config);
+        assert!(changes.entries.is_empty());
+        assert!(changes.ambiguous.is_empty());
+
+        // even as often in both logs, values alike but for their code are a guess
+        let val1 = with_codes(&[("Scanner code", "A"), ("Scanner code", "B")]);
+        let val2 = with_codes(&[("Scanner code", "B"), ("Scanner code", "A")]);
+        let changes = diff(&val1, &val2, &config);
+        assert_eq!(changes.ambiguous.iter().map(|a| (a.count1, a.count2)).collect::<Vec<_>>(), vec![(2, 2)]);
+
+        // and otherwise paired in order, the leftovers reported as ambiguous
+        let val1 = with_codes(&[("Scanner code", "A"), ("Scanner code", "B")]);
+        let val2 = with_codes(&[("Scanner code", "A"), ("Scanner code", "C"), ("Scanner code", "D")]);
+        let changes = diff(&val1, &val2, &config);
+        assert_eq!(codes(&changes.entries), vec![(some("B"), some("C")), (None, some("D"))]);
+        assert_eq!(changes.ambiguous, vec![AmbiguousMatch {
+            path: DiffPath { section: "Gateway (A7.1)".to_string(), measurements: vec!["Control unit, coding".to_string()] },
+            item: ItemKind::Value,
+            key: "Batteriewechsel_Technologie_zwei.Scannercode".to_string(),
+            count1: 2,
+            count2: 3,
+        }]);
+        assert_eq!(diff(&val1, &val2, &config), changes);
+    }
+}
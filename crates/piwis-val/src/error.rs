@@ -0,0 +1,82 @@
+use std::fmt;
+use quick_xml::DeError;
+use crate::archive::ProtocolSelector;
+use crate::ParseWarning;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+    /// The file is not well-formed XML.
+    Xml(DeError),
+    /// The XML does not match the expected FAP structure. `path` is the
+    /// serde_path_to_error path of the offending element: element names below
+    /// `FAP` joined by dots, with repeated elements indexed in brackets, e.g.
+    /// `RESULT.SECTION[2].MEAS[1]`. A missing element is reported at its
+    /// parent. Unlike [`ParseWarning`] paths, indices are not dot segments.
+    Schema { path: String, source: DeError },
+    /// The XML contains elements, object types or value formats this crate does
+    /// not know. Loading with `LoadOptions::lenient` keeps them instead.
+    Unsupported(ParseWarning),
+    /// The log could not be written as XML.
+    Write(DeError),
+    /// The archive does not contain a FAP XML file.
+    MissingXml,
+    /// The archive does not contain the requested protocol.
+    MissingProtocol(ProtocolSelector),
+    /// A [`Query`](crate::query::Query) path could not be parsed.
+    InvalidQuery(String, String),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Zip(e) => write!(f, "zip error: {}", e),
+            Error::Xml(e) => write!(f, "invalid XML: {}", e),
+            Error::Schema { path, source } => write!(f, "failed deserializing {}: {}", path, source),
+            Error::Unsupported(warning) => write!(f, "unsupported content at {}", warning),
+            Error::Write(e) => write!(f, "failed writing XML: {}", e),
+            Error::MissingXml => write!(f, "could not find FAP xml in zip"),
+            Error::MissingProtocol(selector) => write!(f, "could not find protocol {} in zip", selector),
+            Error::InvalidQuery(query, message) => write!(f, "invalid query '{}': {}", query, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Zip(e) => Some(e),
+            Error::Xml(e) => Some(e),
+            Error::Schema { source, .. } => Some(source),
+            Error::Write(e) => Some(e),
+            Error::Unsupported(_) | Error::MissingXml | Error::MissingProtocol(_) | Error::InvalidQuery(..) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        Error::Zip(e)
+    }
+}
+
+impl From<serde_path_to_error::Error<DeError>> for Error {
+    fn from(e: serde_path_to_error::Error<DeError>) -> Self {
+        let path = e.path().to_string();
+        match e.into_inner() {
+            e @ (DeError::InvalidXml(_) | DeError::UnexpectedEof) => Error::Xml(e),
+            source => Error::Schema { path, source },
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use serde::Serialize;
+use crate::matching::{ambiguous_keys, pair_in_order};
+use crate::quantity::parse_whole_number;
+use crate::{Measurement, MeasurementExtendedErrorMemory, MeasurementMistake, Section, ValueEnum};
+
+/// Labels or texts under which PIWIS reports the fault (DTC) number.
+const FAULT_CODE_KEYS: &[&str] = &["Fehlernummer", "Fehlercode", "Fault code", "Fault number", "DTC"];
+/// Labels or texts under which PIWIS reports the fault description.
+const FAULT_TEXT_KEYS: &[&str] = &["Fehlertext", "Fault text", "Beschreibung", "Description"];
+const STATUS_KEYS: &[&str] = &["Fehlerstatus", "Fehlerart", "Status", "Fault status", "Fault type", "DTC status"];
+const PRIORITY_KEYS: &[&str] = &["Priority", "Priorität", "Prioritaet", "Hinweis_Prio", "Fehlerpriorität"];
+const OCCURRENCE_KEYS: &[&str] = &["Haeufigkeitszaehler", "Häufigkeitszähler", "Frequency counter", "Occurrence counter", "Fehlerhäufigkeit"];
+const FIRST_ODOMETER_KEYS: &[&str] = &["Km-Stand erstes Auftreten", "Kilometerstand erstes Auftreten", "Mileage first occurrence", "Odometer first occurrence"];
+const LAST_ODOMETER_KEYS: &[&str] = &["Km-Stand letztes Auftreten", "Kilometerstand letztes Auftreten", "Mileage last occurrence", "Odometer last occurrence", "Kilometerstand", "Mileage", "Odometer"];
+const FIRST_SEEN_KEYS: &[&str] = &["Zeitstempel erstes Auftreten", "Date first occurrence", "Time first occurrence"];
+const LAST_SEEN_KEYS: &[&str] = &["Zeitstempel letztes Auftreten", "Date last occurrence", "Time last occurrence", "Zeitstempel", "Time stamp"];
+
+fn matches_key(value: &ValueEnum, keys: &[&str]) -> bool {
+    keys.contains(&value.get_label().as_str()) || keys.contains(&value.get_text().as_str())
+}
+
+fn find_value<'a>(values: &'a [ValueEnum], keys: &[&str]) -> Option<&'a ValueEnum> {
+    values.iter().find(|v| matches_key(v, keys))
+}
+
+fn parse_count(value: &ValueEnum) -> Option<u32> {
+    parse_whole_number(value.get_value()?)?.try_into().ok()
+}
+
+/// Odometer readings are whole numbers, so `12.345` is 12345 km.
+fn parse_odometer(value: &ValueEnum) -> Option<u64> {
+    parse_whole_number(value.get_value()?)?.try_into().ok()
+}
+
+/// Typed view of a stored fault, combining the values of a `Fehler` measurement
+/// with those of its `erweiterter Fehlerspeicher` sub-measurement. Values that
+/// could not be mapped to a field are kept in `environment` (extended fault
+/// memory, usually freeze-frame data) or `remainder` (everything else).
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct FaultEntry {
+    pub code: Option<String>,
+    pub description: Option<String>,
+    pub status: Vec<ValueEnum>,
+    pub priority: Option<u32>,
+    pub occurrences: Option<u32>,
+    pub first_odometer: Option<u64>,
+    pub last_odometer: Option<u64>,
+    pub first_seen: Option<String>,
+    pub last_seen: Option<String>,
+    pub environment: Vec<ValueEnum>,
+    pub remainder: Vec<ValueEnum>,
+}
+
+impl FaultEntry {
+    /// Maps `value` to a field, returning it back if no field applies or its
+    /// contents could not be parsed.
+    fn map_value(&mut self, value: &ValueEnum) -> Option<ValueEnum> {
+        let text = value.get_value().cloned();
+        if matches_key(value, FAULT_CODE_KEYS) && self.code.is_none() {
+            self.code = text;
+        } else if matches_key(value, FAULT_TEXT_KEYS) && self.description.is_none() {
+            self.description = text;
+        } else if matches_key(value, STATUS_KEYS) {
+            self.status.push(value.clone());
+        } else if matches_key(value, PRIORITY_KEYS) && self.priority.is_none() && parse_count(value).is_some() {
+            self.priority = parse_count(value);
+        } else if matches_key(value, OCCURRENCE_KEYS) && self.occurrences.is_none() && parse_count(value).is_some() {
+            self.occurrences = parse_count(value);
+        } else if matches_key(value, FIRST_ODOMETER_KEYS) && self.first_odometer.is_none() && parse_odometer(value).is_some() {
+            self.first_odometer = parse_odometer(value);
+        } else if matches_key(value, LAST_ODOMETER_KEYS) && self.last_odometer.is_none() && parse_odometer(value).is_some() {
+            self.last_odometer = parse_odometer(value);
+        } else if matches_key(value, FIRST_SEEN_KEYS) && self.first_seen.is_none() {
+            self.first_seen = text;
+        } else if matches_key(value, LAST_SEEN_KEYS) && self.last_seen.is_none() {
+            self.last_seen = text;
+        } else {
+            return Some(value.clone());
+        }
+        None
+    }
+
+    fn map_values(&mut self, values: Option<&Vec<ValueEnum>>) -> Vec<ValueEnum> {
+        values.into_iter().flatten().filter_map(|v| self.map_value(v)).collect()
+    }
+
+    fn map_measurements(&mut self, measurements: Option<&Vec<Measurement>>) {
+        for measurement in measurements.into_iter().flatten() {
+            let unmapped = self.map_values(measurement.get_values());
+            self.environment.extend(unmapped);
+            self.map_measurements(measurement.get_submeasurements());
+        }
+    }
+}
+
+impl From<&MeasurementMistake> for FaultEntry {
+    fn from(m: &MeasurementMistake) -> Self {
+        let mut entry = FaultEntry::default();
+        entry.remainder = entry.map_values(m.values.as_ref());
+        entry.map_measurements(m.measurements.as_ref());
+        entry
+    }
+}
+
+impl From<&MeasurementExtendedErrorMemory> for FaultEntry {
+    fn from(m: &MeasurementExtendedErrorMemory) -> Self {
+        let mut entry = FaultEntry::default();
+        entry.environment = entry.map_values(m.values.as_ref());
+        entry
+    }
+}
+
+impl Section {
+    /// Typed views of all faults stored in this section's fault memory.
+    pub fn get_faults(&self) -> Vec<FaultEntry> {
+        self.get_measurements().iter()
+            .filter_map(|m| match m {
+                Measurement::Fehler(fault) => Some(FaultEntry::from(fault)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Identifies a fault by its DTC number, falling back to the first value reported
+/// for it when no known fault number label is present. Faults without any value
+/// have no code and are left to the generic diff.
+pub(crate) fn fault_code(m: &MeasurementMistake) -> Option<&String> {
+    let values = m.values.as_deref().unwrap_or_default();
+    find_value(values, FAULT_CODE_KEYS)
+        .or(values.first())
+        .and_then(|v| v.get_value())
+}
+
+fn faults(section: &Section) -> Vec<(&String, &MeasurementMistake)> {
+    section.get_measurements().iter()
+        .filter_map(|m| match m {
+            Measurement::Fehler(fault) => fault_code(fault).map(|code| (code, fault)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A status, priority, counter or environment value of a persisting fault that
+/// differs between the two logs. `measurements` holds the sub-measurement titles
+/// below the fault, e.g. `erweiterter Fehlerspeicher`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FaultValueChange {
+    pub measurements: Vec<String>,
+    pub old: Option<ValueEnum>,
+    pub new: Option<ValueEnum>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FaultChange {
+    /// Stored in the second log only.
+    Appeared,
+    /// Stored in the first log only.
+    Cleared,
+    /// Stored in both logs.
+    Persisting { changes: Vec<FaultValueChange> },
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FaultDiffEntry {
+    pub section: String,
+    pub code: String,
+    pub description: Option<String>,
+    pub change: FaultChange,
+}
+
+/// Compares the fault memories of two sections describing the same control unit,
+/// matching faults by their DTC number. A fault stored several times is paired
+/// in document order, as are repeated sub-measurements and values below it.
+pub fn diff_faults(section: &Section, other_section: &Section) -> Vec<FaultDiffEntry> {
+    let faults1 = faults(section);
+    let faults2 = faults(other_section);
+    let (pairs, taken) = pair_in_order(&faults1, &faults2, &[&|(a, _), (b, _)| a == b]);
+    let mut entries = vec![];
+
+    for ((code, fault), other) in faults1.iter().zip(pairs) {
+        let change = match other.map(|j| faults2[j].1) {
+            Some(other_fault) => {
+                let mut changes = vec![];
+                diff_fault_values(&[], fault.values.as_deref(), other_fault.values.as_deref(), &mut changes);
+                diff_fault_measurements(&mut vec![], fault.measurements.as_deref(), other_fault.measurements.as_deref(), &mut changes);
+                FaultChange::Persisting { changes }
+            }
+            None => FaultChange::Cleared,
+        };
+        entries.push(FaultDiffEntry {
+            section: section.get_title().clone(),
+            code: (*code).clone(),
+            description: FaultEntry::from(*fault).description,
+            change,
+        });
+    }
+
+    for ((code, fault), _) in faults2.iter().zip(taken).filter(|(_, taken)| !taken) {
+        entries.push(FaultDiffEntry {
+            section: section.get_title().clone(),
+            code: (*code).clone(),
+            description: FaultEntry::from(*fault).description,
+            change: FaultChange::Appeared,
+        });
+    }
+
+    entries
+}
+
+/// DTC numbers stored several times in either section, along with how often
+/// each section stores them. [`diff_faults`] pairs them in document order, which
+/// may pair the wrong ones.
+pub fn ambiguous_faults(section: &Section, other_section: &Section) -> Vec<(String, usize, usize)> {
+    let faults1 = faults(section);
+    let faults2 = faults(other_section);
+    ambiguous_keys(&faults1, &faults2, |(code, _)| code.as_str()).into_iter()
+        .map(|(code, count1, count2)| (code.to_string(), count1, count2))
+        .collect()
+}
+
+fn diff_fault_measurements(path: &mut Vec<String>, measurements: Option<&[Measurement]>, other_measurements: Option<&[Measurement]>, changes: &mut Vec<FaultValueChange>) {
+    let measurements = measurements.unwrap_or_default();
+    let other_measurements = other_measurements.unwrap_or_default();
+    let (pairs, taken) = pair_in_order(measurements, other_measurements, &[&|a, b| a.get_title() == b.get_title()]);
+    for (measurement, other) in measurements.iter().zip(pairs) {
+        path.push(measurement.get_title().clone());
+        let other_measurement = other.map(|j| &other_measurements[j]);
+        diff_fault_values(path, measurement.get_values().map(Vec::as_slice), other_measurement.and_then(|m| m.get_values()).map(Vec::as_slice), changes);
+        diff_fault_measurements(path, measurement.get_submeasurements().map(Vec::as_slice), other_measurement.and_then(|m| m.get_submeasurements()).map(Vec::as_slice), changes);
+        path.pop();
+    }
+    for (other_measurement, _) in other_measurements.iter().zip(taken).filter(|(_, taken)| !taken) {
+        path.push(other_measurement.get_title().clone());
+        diff_fault_values(path, None, other_measurement.get_values().map(Vec::as_slice), changes);
+        diff_fault_measurements(path, None, other_measurement.get_submeasurements().map(Vec::as_slice), changes);
+        path.pop();
+    }
+}
+
+fn diff_fault_values(path: &[String], values: Option<&[ValueEnum]>, other_values: Option<&[ValueEnum]>, changes: &mut Vec<FaultValueChange>) {
+    let values = values.unwrap_or_default();
+    let other_values = other_values.unwrap_or_default();
+    let (pairs, taken) = pair_in_order(values, other_values, &[
+        &|a, b| a.get_label() == b.get_label() && a.get_text() == b.get_text(),
+        &|a, b| a.get_label() == b.get_label(),
+    ]);
+    for (value, other) in values.iter().zip(pairs) {
+        let other_value = other.map(|j| &other_values[j]);
+        if other_value.map(|v| v.get_value()) != Some(value.get_value()) {
+            changes.push(FaultValueChange { measurements: path.to_vec(), old: Some(value.clone()), new: other_value.cloned() });
+        }
+    }
+    for (other_value, _) in other_values.iter().zip(taken).filter(|(_, taken)| !taken) {
+        changes.push(FaultValueChange { measurements: path.to_vec(), old: None, new: Some(other_value.clone()) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_test_xml;
+    use crate::diff::{diff, ChangeKind, DiffConfig, ItemKind};
+    use crate::NumberValue;
+
+    #[test]
+    fn test_diff_faults() {
+        let val1 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let val2 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_2.xml"));
+        let section1 = val1.get_section_by_title("Airbag (variant: A2.8)").unwrap();
+        let section2 = val2.get_section_by_title("Airbag (variant: A2.8)").unwrap();
+
+        let entries = diff_faults(section1, section2);
+        let summary: Vec<_> = entries.iter().map(|e| (e.code.as_str(), &e.change)).collect();
+        assert_eq!(summary, vec![
+            ("B200000", &FaultChange::Persisting {
+                changes: vec![FaultValueChange {
+                    measurements: vec!["erweiterter Fehlerspeicher".to_string()],
+                    old: Some(ValueEnum::Num(NumberValue {
+                        text: "Frequency counter".to_string(),
+                        unit: None,
+                        label: "Haeufigkeitszaehler".to_string(),
+                        value: "3".to_string(),
+                    })),
+                    new: Some(ValueEnum::Num(NumberValue {
+                        text: "Frequency counter".to_string(),
+                        unit: None,
+                        label: "Haeufigkeitszaehler".to_string(),
+                        value: "4".to_string(),
+                    })),
+                }],
+            }),
+            ("B100100", &FaultChange::Cleared),
+            ("U011100", &FaultChange::Appeared),
+        ]);
+        assert_eq!(entries[0].description.as_deref(), Some("Seat occupancy sensor, passenger"));
+    }
+
+    #[test]
+    fn test_diff_faults_nested() {
+        let val1 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let xml = include_str!("../tests/data/FAP_SAMPLE_2.xml").replace(
+            r#"<VALUE FORMAT="ALPHA" TEXT="Fault text" LABEL="Fehlertext">Seat occupancy sensor, passenger</VALUE>"#,
+            r#"<VALUE FORMAT="ALPHA" TEXT="Fault text" LABEL="Fehlertext">Seat occupancy sensor, passenger</VALUE>
+        <MEAS OBJECT="Erweiterter Fehlerspeicher">
+          <TITLE>Umgebungsbedingungen</TITLE>
+          <MEAS OBJECT="Erweiterter Fehlerspeicher">
+            <TITLE>Snapshot</TITLE>
+            <VALUE FORMAT="NUM" TEXT="Temperature" LABEL="Temperatur" UNIT="°C">21</VALUE>
+          </MEAS>
+        </MEAS>"#);
+        let val2 = load_test_xml(&xml);
+        let section1 = val1.get_section_by_title("Airbag (variant: A2.8)").unwrap();
+        let section2 = val2.get_section_by_title("Airbag (variant: A2.8)").unwrap();
+
+        let FaultChange::Persisting { changes } = &diff_faults(section1, section2)[0].change else {
+            panic!("expected persisting fault");
+        };
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].measurements, vec!["Umgebungsbedingungen", "Snapshot"]);
+        assert_eq!((changes[1].old.as_ref(), changes[1].new.as_ref().map(|v| v.get_label().as_str())), (None, Some("Temperatur")));
+    }
+
+    #[test]
+    fn test_fault_entry() {
+        let val = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let faults = val.get_section_by_title("Airbag (variant: A2.8)").unwrap().get_faults();
+
+        assert_eq!(faults.len(), 2);
+        assert_eq!(faults[0], FaultEntry {
+            code: Some("B200000".to_string()),
+            description: Some("Seat occupancy sensor, passenger".to_string()),
+            priority: Some(2),
+            occurrences: Some(3),
+            ..FaultEntry::default()
+        });
+        assert_eq!(faults[1].code.as_deref(), Some("B100100"));
+    }
+
+    #[test]
+    fn test_ambiguous_faults() {
+        let val1 = load_test_xml(&include_str!("../tests/data/FAP_SAMPLE_1.xml").replace("B100100", "B200000"));
+        let val2 = load_test_xml(&include_str!("../tests/data/FAP_SAMPLE_2.xml").replace("U011100", "B200000"));
+        let section1 = val1.get_section_by_title("Airbag (variant: A2.8)").unwrap();
+        let section2 = val2.get_section_by_title("Airbag (variant: A2.8)").unwrap();
+
+        // stored twice in both logs, still a guess
+        assert_eq!(ambiguous_faults(section1, section2), vec![("B200000".to_string(), 2, 2)]);
+        assert_eq!(ambiguous_faults(section1, section1).len(), 1);
+
+        let config = DiffConfig::new(false, true, false, false, false);
+        let records = diff(&val1, &val2, &config).records();
+        let ambiguous: Vec<_> = records.iter().filter(|r| r.kind == ChangeKind::Ambiguous).collect();
+        assert_eq!(ambiguous.len(), 1);
+        assert_eq!((ambiguous[0].item, ambiguous[0].section.as_str()), (ItemKind::Fault, "Airbag (variant: A2.8)"));
+        assert_eq!(ambiguous[0].measurements, vec!["B200000"]);
+        assert_eq!((ambiguous[0].old.as_deref(), ambiguous[0].new.as_deref()), (Some("2"), Some("2")));
+    }
+
+    #[test]
+    fn test_odometer() {
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml").replace(
+            r#"LABEL="Haeufigkeitszaehler">3</VALUE>"#,
+            r#"LABEL="Haeufigkeitszaehler">3</VALUE>
+          <VALUE FORMAT="NUM" TEXT="Mileage first occurrence" LABEL="Km-Stand erstes Auftreten" UNIT="km">12.345</VALUE>
+          <VALUE FORMAT="NUM" TEXT="Mileage last occurrence" LABEL="Km-Stand letztes Auftreten" UNIT="km">12,4</VALUE>"#);
+        let val = load_test_xml(&xml);
+        let section = val.get_section_by_title("Airbag (variant: A2.8)").unwrap();
+        let fault = &section.get_faults()[0];
+        assert_eq!(fault.first_odometer, Some(12345));
+        assert_eq!(fault.last_odometer, None);
+
+        // a reading that is not a whole number is kept as a value
+        let value = fault.environment.iter().chain(&fault.remainder).find(|v| v.get_text() == "Mileage last occurrence").unwrap();
+        assert_eq!(value.get_quantity().unwrap().value, 12.4);
+    }
+}
@@ -0,0 +1,170 @@
+use chrono::{DateTime, FixedOffset, TimeZone};
+use serde::Serialize;
+use crate::{Header, UnitString, VehicleAnalysisLog};
+
+// Fields that differ between any two test runs and are summarized by
+// `HeaderDiff::time_between_tests` instead.
+const TEST_TIME_FIELDS: &[&str] = &["start_test", "end_test"];
+
+/// A results header or test header field, flattened to its display value.
+/// Values with a unit are joined with it, e.g. `12345 km`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HeaderField {
+    pub field: &'static str,
+    pub value: String,
+}
+
+fn with_unit(value: &UnitString) -> String {
+    format!("{} {}", value.value, value.unit)
+}
+
+impl Header {
+    /// Start of the test, `None` if it does not exist in the log's timezone.
+    pub fn start_time(&self) -> Option<DateTime<FixedOffset>> {
+        self.timezone.from_local_datetime(&self.start_test).single()
+    }
+
+    pub fn end_time(&self) -> Option<DateTime<FixedOffset>> {
+        self.timezone.from_local_datetime(&self.end_test).single()
+    }
+}
+
+impl VehicleAnalysisLog {
+    /// Vehicle, dealer, test and tester data of the log, in document order.
+    pub fn header_fields(&self) -> Vec<HeaderField> {
+        let country = &self.results_header.country;
+        let dealer = &self.results_header.car_dealer;
+        let vehicle = &self.results_header.vehicle;
+        let header = &self.result.header;
+        let equipment = &header.equipment;
+        let fields = [
+            ("regulation", country.regulation.clone()),
+            ("language", country.language.clone()),
+            ("dealer.name", dealer.name.clone()),
+            ("dealer.company", dealer.company.clone()),
+            ("dealer.address", dealer.address.clone()),
+            ("dealer.zip", dealer.zip.clone()),
+            ("dealer.city", dealer.city.clone()),
+            ("dealer.tel", dealer.tel.clone()),
+            ("dealer.dealer_no", dealer.dealer_no.clone()),
+            ("dealer.order", dealer.order.clone()),
+            ("dealer.warranty_no", dealer.warranty_no.clone()),
+            ("vin", vehicle.ident.vin.clone()),
+            ("registration", vehicle.ident.registration.clone()),
+            ("odometer", with_unit(&vehicle.data.odometer)),
+            ("operating_time", with_unit(&vehicle.data.operating_time)),
+            ("order_type", vehicle.data.order_type.clone()),
+            ("model", vehicle.data.model.clone().unwrap_or_default()),
+            ("model_type", vehicle.data.model_type.clone()),
+            ("engine_type", vehicle.data.engine_type.clone()),
+            ("country_code", vehicle.data.country_code.clone()),
+            ("gearbox_type", vehicle.data.gearbox_type.clone()),
+            ("onboard_voltage", with_unit(&vehicle.data.onboard_voltage)),
+            ("title", self.result.title.clone()),
+            ("start_test", header.start_test.format("%d.%m.%Y %H:%M:%S").to_string()),
+            ("end_test", header.end_test.format("%d.%m.%Y %H:%M:%S").to_string()),
+            ("timezone", format!("GMT{}", header.timezone)),
+            ("protocol_type", header.protocol_type.clone()),
+            ("tester.type", equipment._type.clone()),
+            ("tester.title", equipment.title.clone()),
+            ("tester.manufacturer", equipment.manufacturer.clone()),
+            ("tester.model", equipment.model.clone()),
+            ("tester.serial_no", equipment.serial_no.clone()),
+            ("tester.firmware", equipment.firmware.clone()),
+            ("tester.version", equipment.version.clone()),
+            ("tester.pt2g_version", equipment.pt2g_version.clone()),
+            ("tester.br_pdx", equipment.br_pdx.clone()),
+            ("tester.pdu_api", equipment.pdu_api.clone()),
+            ("tester.samdiax_version", equipment.samdiax_version.clone()),
+            ("tester.system", equipment.system.clone()),
+            ("tester.java", equipment.java.clone()),
+            ("tester.mode", equipment.mode.clone()),
+        ];
+        fields.into_iter().map(|(field, value)| HeaderField { field, value }).collect()
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HeaderChange {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+    /// `new - old` for the odometer and operating time, in the first log's unit,
+    /// when both are numbers in compatible units.
+    pub delta: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct HeaderDiff {
+    /// Set when the logs were read from different vehicles.
+    pub vin_mismatch: bool,
+    /// Seconds from the start of the first test to the start of the second.
+    pub time_between_tests: Option<i64>,
+    pub changes: Vec<HeaderChange>,
+}
+
+impl HeaderDiff {
+    pub fn is_empty(&self) -> bool {
+        !self.vin_mismatch && self.changes.is_empty()
+    }
+}
+
+fn delta(old: &UnitString, new: &UnitString) -> Option<f64> {
+    let old = old.quantity()?;
+    Some(new.quantity()?.to_unit_of(&old)?.value - old.value)
+}
+
+/// Compares the results and test headers of two logs. Test start and end times
+/// always differ and are only reported through `time_between_tests`.
+pub fn diff_header(val1: &VehicleAnalysisLog, val2: &VehicleAnalysisLog) -> HeaderDiff {
+    let data1 = &val1.results_header.vehicle.data;
+    let data2 = &val2.results_header.vehicle.data;
+    let changes = val1.header_fields().into_iter()
+        .zip(val2.header_fields())
+        .filter(|(old, new)| old.value != new.value && !TEST_TIME_FIELDS.contains(&old.field))
+        .map(|(old, new)| HeaderChange {
+            field: old.field,
+            delta: match old.field {
+                "odometer" => delta(&data1.odometer, &data2.odometer),
+                "operating_time" => delta(&data1.operating_time, &data2.operating_time),
+                _ => None,
+            },
+            old: old.value,
+            new: new.value,
+        })
+        .collect();
+    let time_between_tests = val1.result.header.start_time()
+        .zip(val2.result.header.start_time())
+        .map(|(start1, start2)| (start2 - start1).num_seconds());
+
+    HeaderDiff {
+        vin_mismatch: val1.results_header.vehicle.ident.vin != val2.results_header.vehicle.ident.vin,
+        time_between_tests,
+        changes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_test_xml;
+
+    #[test]
+    fn test_diff_header() {
+        let val1 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let val2 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_2.xml"));
+
+        let fields = val1.header_fields();
+        assert_eq!(fields.iter().find(|f| f.field == "odometer").unwrap().value, "12345 km");
+        assert_eq!(fields.iter().find(|f| f.field == "tester.pt2g_version").unwrap().value, "42.200.010");
+
+        let header_diff = diff_header(&val1, &val2);
+        assert!(!header_diff.vin_mismatch);
+        assert_eq!(header_diff.time_between_tests, Some(2 * 3600 + 21 * 60 + 28));
+        let odometer = header_diff.changes.iter().find(|c| c.field == "odometer").unwrap();
+        assert_eq!((odometer.old.as_str(), odometer.new.as_str(), odometer.delta), ("12345 km", "12361 km", Some(16.0)));
+        assert!(header_diff.changes.iter().all(|c| !TEST_TIME_FIELDS.contains(&c.field)));
+
+        assert!(diff_header(&val1, &val1).is_empty());
+    }
+}
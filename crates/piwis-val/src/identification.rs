@@ -0,0 +1,190 @@
+use serde::Serialize;
+use crate::matching::SectionMatching;
+use crate::{ECUSection, Measurement, Section, ValueEnum, VehicleAnalysisLog};
+
+// Label variants seen across PIWIS versions, most specific first. Each entry is
+// matched against the value label and, failing that, the value text.
+const PART_NUMBER_KEYS: &[&str] = &["VWTeilenummer", "VW/Audi part number", "VW spare part number", "Porsche part number", "Teilenummer", "Ersatzteilnummer", "Part number"];
+const HARDWARE_PART_NUMBER_KEYS: &[&str] = &["Hardwareteilenummer", "VW hardware part number", "Hardware part number"];
+const HARDWARE_VERSION_KEYS: &[&str] = &["Hardwareversion", "Hardwareversionsnummer", "Hardware version", "Hardware version number"];
+const SOFTWARE_PART_NUMBER_KEYS: &[&str] = &["Softwareteilenummer", "VW software part number", "Software part number"];
+const SOFTWARE_VERSION_KEYS: &[&str] = &["Softwareversion", "Softwareversionsnummer", "Software version", "Software version number"];
+pub(crate) const SERIAL_NUMBER_KEYS: &[&str] = &["Seriennummer", "Steuergeräte-Seriennummer", "Serial number", "ECU serial number"];
+const CODING_INDEX_KEYS: &[&str] = &["Codierindex", "Kodierindex", "Coding index"];
+const SUPPLIER_KEYS: &[&str] = &["Lieferant", "Systemlieferant", "Hersteller", "Supplier", "Manufacturer"];
+const SYSTEM_NAME_KEYS: &[&str] = &["Systembezeichnung", "Systemname", "VW system name", "Porsche system name", "System name", "System designation"];
+
+/// Typed view of the identification data a control unit reports.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct EcuIdentification {
+    pub part_number: Option<String>,
+    pub hardware_part_number: Option<String>,
+    pub hardware_version: Option<String>,
+    pub software_part_number: Option<String>,
+    pub software_version: Option<String>,
+    pub serial_number: Option<String>,
+    pub coding_index: Option<String>,
+    pub supplier: Option<String>,
+    pub system_name: Option<String>,
+}
+
+fn lookup(values: &[&ValueEnum], keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .find_map(|key| values.iter().find(|v| v.get_label() == key)
+            .or_else(|| values.iter().find(|v| v.get_text() == key)))
+        .and_then(|v| v.get_value().cloned())
+}
+
+impl EcuIdentification {
+    fn from_values(values: &[&ValueEnum]) -> EcuIdentification {
+        EcuIdentification {
+            part_number: lookup(values, PART_NUMBER_KEYS),
+            hardware_part_number: lookup(values, HARDWARE_PART_NUMBER_KEYS),
+            hardware_version: lookup(values, HARDWARE_VERSION_KEYS),
+            software_part_number: lookup(values, SOFTWARE_PART_NUMBER_KEYS),
+            software_version: lookup(values, SOFTWARE_VERSION_KEYS),
+            serial_number: lookup(values, SERIAL_NUMBER_KEYS),
+            coding_index: lookup(values, CODING_INDEX_KEYS),
+            supplier: lookup(values, SUPPLIER_KEYS),
+            system_name: lookup(values, SYSTEM_NAME_KEYS),
+        }
+    }
+}
+
+impl ECUSection {
+    /// Identification data gathered from all `Identifikation` measurements, or
+    /// `None` if the control unit's identification was not read.
+    pub fn get_identification(&self) -> Option<EcuIdentification> {
+        let values: Vec<_> = self.measurements.iter()
+            .filter_map(|m| match m {
+                Measurement::Identifikation(identification) => Some(identification.values.iter().flatten()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+        Some(EcuIdentification::from_values(&values))
+    }
+}
+
+impl Section {
+    pub fn get_identification(&self) -> Option<EcuIdentification> {
+        match self {
+            Section::ECU(section) => section.get_identification(),
+            Section::Unknown(_) => None,
+        }
+    }
+}
+
+/// The identification fields that change when a control unit is flashed or
+/// replaced.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct FirmwareLevel {
+    pub software_version: Option<String>,
+    pub software_part_number: Option<String>,
+    pub hardware_version: Option<String>,
+}
+
+impl From<&EcuIdentification> for FirmwareLevel {
+    fn from(identification: &EcuIdentification) -> Self {
+        FirmwareLevel {
+            software_version: identification.software_version.clone(),
+            software_part_number: identification.software_part_number.clone(),
+            hardware_version: identification.hardware_version.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FirmwareChange {
+    /// Control unit present in the second log only.
+    Appeared { new: FirmwareLevel },
+    /// Control unit present in the first log only.
+    Disappeared { old: FirmwareLevel },
+    Changed { old: FirmwareLevel, new: FirmwareLevel },
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FirmwareDiffEntry {
+    pub section: String,
+    pub change: FirmwareChange,
+}
+
+fn firmware_level(section: &Section) -> FirmwareLevel {
+    section.get_identification().as_ref().map(FirmwareLevel::from).unwrap_or_default()
+}
+
+/// Reports control units whose software or hardware level differs between the
+/// two logs, along with control units found in only one of them. Sections are
+/// paired by `matching`.
+pub fn diff_firmware(val1: &VehicleAnalysisLog, val2: &VehicleAnalysisLog, matching: &SectionMatching) -> Vec<FirmwareDiffEntry> {
+    let mut entries = vec![];
+    for pair in matching.pair(val1, val2) {
+        let (section, change) = match pair {
+            (Some(section), Some(other_section)) => {
+                let old = firmware_level(section);
+                let new = firmware_level(other_section);
+                if old == new {
+                    continue;
+                }
+                (section, FirmwareChange::Changed { old, new })
+            }
+            (Some(section), None) => (section, FirmwareChange::Disappeared { old: firmware_level(section) }),
+            (None, Some(other_section)) => (other_section, FirmwareChange::Appeared { new: firmware_level(other_section) }),
+            (None, None) => continue,
+        };
+        entries.push(FirmwareDiffEntry { section: section.get_title().clone(), change });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_test_xml;
+
+    #[test]
+    fn test_identification() {
+        let val = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+
+        let identification = val.get_section_by_title("Gateway (A7.1)").unwrap().get_identification().unwrap();
+        assert_eq!(identification, EcuIdentification {
+            part_number: Some("9Y0907530AB".to_string()),
+            hardware_version: Some("H05".to_string()),
+            software_version: Some("0456".to_string()),
+            serial_number: Some("00001234567890".to_string()),
+            ..EcuIdentification::default()
+        });
+
+        assert_eq!(val.get_section_by_title("Rear-view camera").unwrap().get_identification(), None);
+    }
+
+    #[test]
+    fn test_diff_firmware() {
+        let val1 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let val2 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_2.xml"));
+
+        let level = |software_version: &str| FirmwareLevel {
+            software_version: Some(software_version.to_string()),
+            hardware_version: Some("H05".to_string()),
+            ..FirmwareLevel::default()
+        };
+        assert_eq!(diff_firmware(&val1, &val2, &SectionMatching::default()), vec![
+            FirmwareDiffEntry {
+                section: "Gateway (A7.1)".to_string(),
+                change: FirmwareChange::Changed { old: level("0456"), new: level("0460") },
+            },
+            FirmwareDiffEntry {
+                section: "Rear-view camera".to_string(),
+                change: FirmwareChange::Disappeared { old: FirmwareLevel::default() },
+            },
+            FirmwareDiffEntry {
+                section: "Parking assistant".to_string(),
+                change: FirmwareChange::Appeared { new: FirmwareLevel::default() },
+            },
+        ]);
+    }
+}
@@ -1,41 +1,349 @@
+use std::fmt;
 use std::fs::File;
-use std::io::BufReader;
-use anyhow::Context;
+use std::io::{BufRead, BufReader, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
 use chrono::{FixedOffset, NaiveDateTime};
+use serde::de::{MapAccess, Visitor};
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_untagged::UntaggedEnumVisitor;
+use crate::archive::ProtocolSelector;
+
+pub mod anonymize;
+pub mod archive;
+pub mod diff;
+mod error;
+pub mod faults;
+pub mod header;
+pub mod identification;
+pub mod matching;
+pub mod quantity;
+pub mod query;
+pub mod rules;
+pub mod walk;
+mod xml;
+
+pub use error::{Error, Result};
 
 #[derive(Deserialize, Serialize, Debug)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct VehicleAnalysisLog {
     #[serde(rename = "RESULTSHEADER")]
     pub results_header: ResultsHeader,
     pub result: ValResult,
+    /// Elements and object types that were skipped while loading with
+    /// [`LoadOptions::lenient`].
+    #[serde(skip)]
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// Something in the FAP XML this crate does not know about. `path` uses the
+/// element names and indices of the XML, e.g. `RESULT.SECTION.3.MEAS.0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// An element this crate does not know, found in a section or measurement and
+/// kept as is by lenient loads so that it is written back. Attributes and text
+/// are children named `@name` and `$text`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RawElement {
+    pub name: String,
+    pub content: RawContent,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum RawContent {
+    Text(String),
+    Children(Vec<RawElement>),
+}
+
+impl<'de> Deserialize<'de> for RawContent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawVisitor;
+
+        impl<'de> Visitor<'de> for RawVisitor {
+            type Value = RawContent;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("XML content")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(RawContent::Text(value.to_string()))
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E> {
+                Ok(RawContent::Children(vec![]))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut children = vec![];
+                while let Some((name, content)) = map.next_entry()? {
+                    children.push(RawElement { name, content });
+                }
+                Ok(RawContent::Children(children))
+            }
+        }
+
+        deserializer.deserialize_any(RawVisitor)
+    }
+}
+
+/// Controls how a FAP XML is loaded. By default unknown elements and section,
+/// measurement or value types are errors. A lenient load keeps them instead and
+/// records a [`ParseWarning`] for each: unknown types as `Unknown` sections,
+/// measurements and values, unknown elements and attributes of sections and
+/// measurements as [`RawElement`]s. Other unknown elements are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub lenient: bool,
+    /// Protocol to load from archives holding several; the first one otherwise.
+    pub protocol: Option<ProtocolSelector>,
+}
+
+impl LoadOptions {
+    pub fn lenient() -> LoadOptions {
+        LoadOptions { lenient: true, ..LoadOptions::default() }
+    }
+
+    pub fn load_zip(&self, filename: impl AsRef<Path>) -> Result<VehicleAnalysisLog> {
+        self.load_zip_reader(File::open(filename)?)
+    }
+
+    /// Loads the FAP XML picked by `protocol` from a zip archive read from `reader`.
+    /// Other entries are skipped without being parsed.
+    pub fn load_zip_reader<R: Read + Seek>(&self, reader: R) -> Result<VehicleAnalysisLog> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let files = (0..archive.len()).filter_map(|i| read_xml_entry(&mut archive, i).transpose());
+        self.load_protocol(files)
+    }
+
+    /// Loads a bare, already extracted FAP XML file.
+    pub fn load_xml(&self, filename: impl AsRef<Path>) -> Result<VehicleAnalysisLog> {
+        self.load_xml_reader(BufReader::new(File::open(filename)?))
+    }
+
+    /// Loads the FAP XML picked by `protocol`, or the first one, from a directory
+    /// holding an extracted archive, searching subdirectories in name order.
+    pub fn load_dir(&self, dirname: impl AsRef<Path>) -> Result<VehicleAnalysisLog> {
+        let files = find_files(dirname.as_ref())?.into_iter().filter(|path| has_xml_name(path)).map(|path| {
+            let data = std::fs::read(&path)?;
+            Ok((path.to_string_lossy().into_owned(), data))
+        });
+        self.load_protocol(files)
+    }
+
+    /// Loads a zip archive or a bare FAP XML held in memory, telling them apart by
+    /// the zip signature.
+    pub fn load_bytes(&self, bytes: &[u8]) -> Result<VehicleAnalysisLog> {
+        if bytes.starts_with(ZIP_SIGNATURE) {
+            self.load_zip_reader(Cursor::new(bytes))
+        } else {
+            self.load_xml_reader(bytes)
+        }
+    }
+
+    /// Loads a zip archive, a bare FAP XML or an extracted directory, depending on
+    /// what `path` points to.
+    pub fn load_path(&self, path: impl AsRef<Path>) -> Result<VehicleAnalysisLog> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            return self.load_dir(path);
+        }
+        if is_zip_file(path)? {
+            self.load_zip(path)
+        } else {
+            self.load_xml(path)
+        }
+    }
+
+    pub fn load_xml_reader<R: BufRead>(&self, reader: R) -> Result<VehicleAnalysisLog> {
+        let mut warnings = vec![];
+        // `?` marks `Option` layers in serde_ignored paths, which have no XML counterpart
+        let mut ignored = |path: serde_ignored::Path| warnings.push(ParseWarning {
+            path: path.to_string().replace(".?", ""),
+            message: "unknown element".to_string(),
+        });
+        let de = &mut quick_xml::de::Deserializer::from_reader(reader);
+        let mut val: VehicleAnalysisLog = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(de, &mut ignored))?;
+        warnings.extend(val.unknown_objects());
+
+        if !self.lenient {
+            if let Some(warning) = warnings.into_iter().next() {
+                return Err(Error::Unsupported(warning));
+            }
+            return Ok(val);
+        }
+        val.warnings = warnings;
+        Ok(val)
+    }
+}
+
+pub(crate) const ZIP_SIGNATURE: &[u8; 4] = b"PK\x03\x04";
+
+/// Whether a file or archive entry name has the `.xml` extension, in any case.
+pub(crate) fn is_xml_name(name: &str) -> bool {
+    Path::new(name).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("xml"))
+}
+
+// Name and, for XML files, contents of an archive entry; `None` for directories.
+fn read_xml_entry<R: Read + Seek>(archive: &mut zip::ZipArchive<R>, index: usize) -> Result<Option<(String, Vec<u8>)>> {
+    let mut file = archive.by_index(index)?;
+    if file.is_dir() {
+        return Ok(None);
+    }
+    let mut data = vec![];
+    if is_xml_name(file.name()) {
+        file.read_to_end(&mut data)?;
+    }
+    Ok(Some((file.name().to_string(), data)))
+}
+
+pub(crate) fn is_zip_file(path: &Path) -> Result<bool> {
+    let mut signature = [0u8; 4];
+    Ok(File::open(path)?.read_exact(&mut signature).is_ok() && &signature == ZIP_SIGNATURE)
+}
+
+fn has_xml_name(path: &Path) -> bool {
+    path.file_name().and_then(|name| name.to_str()).is_some_and(is_xml_name)
+}
+
+// Files of a directory in name order, followed by those of its subdirectories.
+pub(crate) fn find_files(dirname: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries: Vec<_> = std::fs::read_dir(dirname)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    let mut files: Vec<_> = entries.iter().filter(|path| path.is_file()).cloned().collect();
+    for path in entries.iter().filter(|p| p.is_dir()) {
+        files.extend(find_files(path)?);
+    }
+    Ok(files)
+}
+
+fn unknown_elements(path: &str, elements: &[RawElement], warnings: &mut Vec<ParseWarning>) {
+    for element in elements {
+        warnings.push(ParseWarning { path: format!("{}.{}", path, element.name), message: "unknown element".to_string() });
+    }
+}
+
+fn unknown_measurements(path: &str, measurements: &[Measurement], warnings: &mut Vec<ParseWarning>) {
+    for (i, measurement) in measurements.iter().enumerate() {
+        let path = format!("{}.MEAS.{}", path, i);
+        if let Measurement::Unknown(m) = measurement {
+            warnings.push(ParseWarning { path: path.clone(), message: format!("unknown measurement object '{}'", m.object) });
+        }
+        unknown_elements(&path, measurement.get_unknown_elements(), warnings);
+        for (j, value) in measurement.get_values().into_iter().flatten().enumerate() {
+            if let ValueEnum::Unknown(v) = value {
+                warnings.push(ParseWarning { path: format!("{}.VALUE.{}", path, j), message: format!("unknown value format '{}'", v.format) });
+            }
+        }
+        if let Some(submeasurements) = measurement.get_submeasurements() {
+            unknown_measurements(&path, submeasurements, warnings);
+        }
+    }
 }
 
 impl VehicleAnalysisLog {
-    pub fn from_zip(filename: &str) -> anyhow::Result<VehicleAnalysisLog> {
-        let file = File::open(&filename)?;
-        let mut archive = zip::ZipArchive::new(file)?;
-        for i in 0..archive.len() {
-            let file = archive.by_index(i)?;
-            if file.name().ends_with(".xml") {
-                let reader = BufReader::new(file);
-                let val = &mut quick_xml::de::Deserializer::from_reader(reader);
-                let deserialized: VehicleAnalysisLog = serde_path_to_error::deserialize(val).context("Failed deserializing")?;
-                return Ok(deserialized);
+    pub fn from_zip(filename: impl AsRef<Path>) -> Result<VehicleAnalysisLog> {
+        LoadOptions::default().load_zip(filename)
+    }
+
+    pub fn from_zip_reader<R: Read + Seek>(reader: R) -> Result<VehicleAnalysisLog> {
+        LoadOptions::default().load_zip_reader(reader)
+    }
+
+    pub fn from_xml(filename: impl AsRef<Path>) -> Result<VehicleAnalysisLog> {
+        LoadOptions::default().load_xml(filename)
+    }
+
+    pub fn from_xml_reader<R: BufRead>(reader: R) -> Result<VehicleAnalysisLog> {
+        LoadOptions::default().load_xml_reader(reader)
+    }
+
+    pub fn from_dir(dirname: impl AsRef<Path>) -> Result<VehicleAnalysisLog> {
+        LoadOptions::default().load_dir(dirname)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<VehicleAnalysisLog> {
+        LoadOptions::default().load_bytes(bytes)
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<VehicleAnalysisLog> {
+        LoadOptions::default().load_path(path)
+    }
+
+    fn unknown_objects(&self) -> Vec<ParseWarning> {
+        let mut warnings = vec![];
+        for (i, section) in self.result.sections.iter().enumerate() {
+            let path = format!("RESULT.SECTION.{}", i);
+            if let Section::Unknown(s) = section {
+                warnings.push(ParseWarning { path: path.clone(), message: format!("unknown section object '{}'", s.object) });
             }
+            unknown_elements(&path, section.get_unknown_elements(), &mut warnings);
+            unknown_measurements(&path, section.get_measurements(), &mut warnings);
         }
-        Err(anyhow::anyhow!("Could not find FAP xml in zip."))
+        warnings
     }
 
     pub fn get_section_by_title(&self, title: &str) -> Option<&Section> {
         self.result.sections.iter().find(|s| s.get_title() == title)
     }
+
+    /// Name PIWIS gives the log's files, without extension, e.g.
+    /// `FAP_<VIN>_20240804_132559_23.0.1`.
+    pub fn file_name(&self) -> String {
+        format!("{}_{}_{}_{}",
+                self.result.header.protocol_type,
+                self.results_header.vehicle.ident.vin,
+                self.result.header.end_test.format("%Y%m%d_%H%M%S"),
+                self.result.header.equipment.br_pdx)
+    }
+
+    /// Writes the log back as a FAP XML document, in the element and attribute
+    /// order PIWIS uses.
+    pub fn to_xml(&self) -> Result<String> {
+        xml::to_string(self).map_err(Error::Write)
+    }
+
+    pub fn to_xml_writer<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(self.to_xml()?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the log as a VAL zip holding a single FAP XML named after
+    /// [`VehicleAnalysisLog::file_name`].
+    pub fn to_zip(&self, filename: impl AsRef<Path>) -> Result<()> {
+        self.to_zip_writer(File::create(filename)?)?;
+        Ok(())
+    }
+
+    pub fn to_zip_writer<W: Write + Seek>(&self, writer: W) -> Result<W> {
+        let mut zip = zip::ZipWriter::new(writer);
+        zip.start_file(format!("{}.xml", self.file_name()), zip::write::SimpleFileOptions::default())?;
+        self.to_xml_writer(&mut zip)?;
+        Ok(zip.finish()?)
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct ResultsHeader {
     pub country: Country,
     #[serde(rename = "CARDEALER")]
@@ -44,14 +352,14 @@ pub struct ResultsHeader {
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct Country {
     pub regulation: String,
     pub language: String,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct CarDealer {
     pub name: String,
     pub company: String,
@@ -67,27 +375,28 @@ pub struct CarDealer {
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct Vehicle {
     pub ident: VehicleIdentity,
     pub data: VehicleData,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct VehicleIdentity {
     pub vin: String,
     pub registration: String,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct VehicleData {
     pub odometer: UnitString,
     #[serde(rename = "OPERATINGTIME")]
     pub operating_time: UnitString,
     #[serde(rename = "ORDERTYPE")]
     pub order_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
     #[serde(rename = "MODELTYPE")]
     pub model_type: String,
@@ -102,7 +411,7 @@ pub struct VehicleData {
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct UnitString {
     #[serde(rename = "@UNIT")]
     pub unit: String,
@@ -111,7 +420,7 @@ pub struct UnitString {
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct ValResult {
     #[serde(rename = "@OBJECT")]
     pub object: String,
@@ -124,7 +433,7 @@ pub struct ValResult {
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct Header {
     #[serde(with = "time_format")]
     pub start_test: NaiveDateTime,
@@ -138,7 +447,7 @@ pub struct Header {
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct Equipment {
     #[serde(rename = "@TYPE")]
     pub _type: String,
@@ -242,28 +551,55 @@ mod timezone_format {
 }
 
 #[derive(Serialize, Debug)]
-#[serde(deny_unknown_fields, tag = "@OBJECT")]
+#[serde(tag = "@OBJECT")]
 pub enum Section {
-    ECU(ECUSection)
+    ECU(ECUSection),
+    /// Section with an `@OBJECT` this crate does not know, kept by lenient loads.
+    #[serde(untagged)]
+    Unknown(UnknownSection),
 }
 
 impl Section {
+    /// The section's `@OBJECT` type.
+    pub fn get_object(&self) -> &str {
+        match self {
+            Section::ECU(_) => "ECU",
+            Section::Unknown(section) => &section.object,
+        }
+    }
     pub fn get_title(&self) -> &String {
         match self {
             Section::ECU(section) => &section.title,
+            Section::Unknown(section) => &section.title,
         }
     }
     pub fn get_measurements(&self) -> &Vec<Measurement> {
         match self {
             Section::ECU(section) => &section.measurements,
+            Section::Unknown(section) => &section.measurements,
+        }
+    }
+    pub fn get_measurements_mut(&mut self) -> &mut Vec<Measurement> {
+        match self {
+            Section::ECU(section) => &mut section.measurements,
+            Section::Unknown(section) => &mut section.measurements,
         }
     }
-    #[allow(dead_code)]
-    fn get_measurement_by_title(&self, title: &String) -> Option<Measurement> {
+    pub fn get_unknown_elements(&self) -> &Vec<RawElement> {
         match self {
-            Section::ECU(section) => get_measurement_by_title(&section.measurements, title),
+            Section::ECU(section) => &section.unknown_elements,
+            Section::Unknown(section) => &section.unknown_elements,
         }
     }
+    pub fn get_unknown_elements_mut(&mut self) -> &mut Vec<RawElement> {
+        match self {
+            Section::ECU(section) => &mut section.unknown_elements,
+            Section::Unknown(section) => &mut section.unknown_elements,
+        }
+    }
+    pub fn get_measurement_by_title(&self, title: &str) -> Option<&Measurement> {
+        get_measurement_by_title(self.get_measurements(), title)
+    }
 }
 
 impl<'de> Deserialize<'de> for Section {
@@ -276,17 +612,15 @@ impl<'de> Deserialize<'de> for Section {
                 let value: CommonSection = map.deserialize()?;
                 match value.object.as_str() {
                     "ECU" => Ok(Section::ECU(ECUSection::from(value))),
-                    _ => {
-                        Err(serde::de::Error::custom(format!("'{}' not implemented", value.object.as_str())))
-                    }
+                    _ => Ok(Section::Unknown(UnknownSection::from(value))),
                 }
             })
             .deserialize(deserializer)
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct CommonSection {
     #[serde(rename = "@OBJECT")]
     pub object: String,
@@ -294,6 +628,56 @@ pub struct CommonSection {
     pub title: String,
     #[serde(rename = "MEAS")]
     pub measurements: Vec<Measurement>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_elements: Vec<RawElement>,
+}
+
+// Sections and measurements are read by hand so that elements other than the
+// known ones are kept instead of being skipped.
+impl<'de> Deserialize<'de> for CommonSection {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SectionVisitor;
+
+        impl<'de> Visitor<'de> for SectionVisitor {
+            type Value = CommonSection;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a SECTION element")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let (mut object, mut title, mut measurements) = (None, None, None);
+                let mut unknown_elements = vec![];
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "@OBJECT" => object = Some(map.next_value()?),
+                        "TITLE" => title = Some(map.next_value()?),
+                        "MEAS" => extend(&mut measurements, map.next_value()?),
+                        _ => unknown_elements.push(RawElement { content: map.next_value()?, name: key }),
+                    }
+                }
+                Ok(CommonSection {
+                    object: object.ok_or_else(|| serde::de::Error::missing_field("@OBJECT"))?,
+                    title: title.ok_or_else(|| serde::de::Error::missing_field("TITLE"))?,
+                    measurements: measurements.ok_or_else(|| serde::de::Error::missing_field("MEAS"))?,
+                    unknown_elements,
+                })
+            }
+        }
+
+        deserializer.deserialize_map(SectionVisitor)
+    }
+}
+
+// Lists of elements interrupted by an unknown element are read in several parts.
+fn extend<T>(list: &mut Option<Vec<T>>, items: Vec<T>) {
+    list.get_or_insert_with(Vec::new).extend(items);
 }
 
 #[derive(Serialize, Debug)]
@@ -302,6 +686,8 @@ pub struct ECUSection {
     pub title: String,
     #[serde(rename = "MEAS")]
     pub measurements: Vec<Measurement>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_elements: Vec<RawElement>,
 }
 
 impl From<CommonSection> for ECUSection {
@@ -309,17 +695,36 @@ impl From<CommonSection> for ECUSection {
         ECUSection {
             title: m.title,
             measurements: m.measurements,
+            unknown_elements: m.unknown_elements,
         }
     }
 }
 
-fn get_measurement_by_title(measurements: &Vec<Measurement>, title: &String) -> Option<Measurement> {
-    for m in measurements {
-        if m.get_title() == title {
-            return Some(m.clone());
+#[derive(Serialize, Debug)]
+pub struct UnknownSection {
+    #[serde(rename = "@OBJECT")]
+    pub object: String,
+    #[serde(rename = "TITLE")]
+    pub title: String,
+    #[serde(rename = "MEAS")]
+    pub measurements: Vec<Measurement>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_elements: Vec<RawElement>,
+}
+
+impl From<CommonSection> for UnknownSection {
+    fn from(m: CommonSection) -> Self {
+        UnknownSection {
+            object: m.object,
+            title: m.title,
+            measurements: m.measurements,
+            unknown_elements: m.unknown_elements,
         }
     }
-    None
+}
+
+fn get_measurement_by_title<'a>(measurements: &'a [Measurement], title: &str) -> Option<&'a Measurement> {
+    measurements.iter().find(|m| m.get_title() == title)
 }
 
 #[derive(Serialize, Debug, Clone, PartialEq)]
@@ -329,9 +734,23 @@ pub enum Measurement {
     Fehler(MeasurementMistake),
     Messwerte(MeasurementMeasuredValues),
     ErweiterterFehlerspeicher(MeasurementExtendedErrorMemory),
+    /// Measurement with an `@OBJECT` this crate does not know, kept by lenient loads.
+    Unknown(MeasurementUnknown),
 }
 
 impl Measurement {
+    /// The measurement's `@OBJECT` type.
+    pub fn get_object(&self) -> &str {
+        match self {
+            Measurement::Codierung(_) => "Codierung",
+            Measurement::Identifikation(_) => "Identifikation",
+            Measurement::Fehler(_) => "Fehler",
+            Measurement::Messwerte(_) => "Messwerte",
+            Measurement::ErweiterterFehlerspeicher(_) => "Erweiterter Fehlerspeicher",
+            Measurement::Unknown(m) => &m.object,
+        }
+    }
+
     pub fn get_title(&self) -> &String {
         match self {
             Measurement::Codierung(m) => &m.title,
@@ -339,6 +758,7 @@ impl Measurement {
             Measurement::Fehler(m) => &m.title,
             Measurement::Messwerte(m) => &m.title,
             Measurement::ErweiterterFehlerspeicher(m) => &m.title,
+            Measurement::Unknown(m) => &m.title,
         }
     }
 
@@ -349,35 +769,76 @@ impl Measurement {
             Measurement::Fehler(m) => m.values.as_ref(),
             Measurement::Messwerte(m) => m.values.as_ref(),
             Measurement::ErweiterterFehlerspeicher(m) => m.values.as_ref(),
+            Measurement::Unknown(m) => m.values.as_ref(),
+        }
+    }
+
+    pub fn get_values_mut(&mut self) -> Option<&mut Vec<ValueEnum>> {
+        match self {
+            Measurement::Codierung(m) => m.values.as_mut(),
+            Measurement::Identifikation(m) => m.values.as_mut(),
+            Measurement::Fehler(m) => m.values.as_mut(),
+            Measurement::Messwerte(m) => m.values.as_mut(),
+            Measurement::ErweiterterFehlerspeicher(m) => m.values.as_mut(),
+            Measurement::Unknown(m) => m.values.as_mut(),
         }
     }
 
-    #[allow(dead_code)]
-    fn get_value_by_label(&self, label: &String) -> Option<&ValueEnum> {
+    pub fn get_value_by_label(&self, label: &str) -> Option<&ValueEnum> {
         match self.get_values() {
-            Some(values) => values.iter().find(|v| match v {
-                ValueEnum::Num(n) => &n.label == label,
-                ValueEnum::Alpha(a) => &a.label == label,
-            }),
+            Some(values) => values.iter().find(|v| v.get_label() == label),
             _ => None,
         }
     }
 
     pub fn get_submeasurements(&self) -> Option<&Vec<Measurement>> {
         match self {
+            Measurement::Codierung(m) => m.measurements.as_ref(),
+            Measurement::Identifikation(m) => m.measurements.as_ref(),
             Measurement::Fehler(m) => m.measurements.as_ref(),
-            _ => None,
+            Measurement::Messwerte(m) => m.measurements.as_ref(),
+            Measurement::ErweiterterFehlerspeicher(m) => m.measurements.as_ref(),
+            Measurement::Unknown(m) => m.measurements.as_ref(),
         }
     }
 
-    #[allow(dead_code)]
-    fn get_submeasurement_by_title(&self, title: &String) -> Option<Measurement> {
+    pub fn get_submeasurements_mut(&mut self) -> Option<&mut Vec<Measurement>> {
         match self {
-            Measurement::Fehler(m) => match &m.measurements {
-                Some(measurements) => get_measurement_by_title(&measurements, title),
-                _ => None,
-            }
-            _ => None
+            Measurement::Codierung(m) => m.measurements.as_mut(),
+            Measurement::Identifikation(m) => m.measurements.as_mut(),
+            Measurement::Fehler(m) => m.measurements.as_mut(),
+            Measurement::Messwerte(m) => m.measurements.as_mut(),
+            Measurement::ErweiterterFehlerspeicher(m) => m.measurements.as_mut(),
+            Measurement::Unknown(m) => m.measurements.as_mut(),
+        }
+    }
+
+    pub fn get_unknown_elements(&self) -> &Vec<RawElement> {
+        match self {
+            Measurement::Codierung(m) => &m.unknown_elements,
+            Measurement::Identifikation(m) => &m.unknown_elements,
+            Measurement::Fehler(m) => &m.unknown_elements,
+            Measurement::Messwerte(m) => &m.unknown_elements,
+            Measurement::ErweiterterFehlerspeicher(m) => &m.unknown_elements,
+            Measurement::Unknown(m) => &m.unknown_elements,
+        }
+    }
+
+    pub fn get_unknown_elements_mut(&mut self) -> &mut Vec<RawElement> {
+        match self {
+            Measurement::Codierung(m) => &mut m.unknown_elements,
+            Measurement::Identifikation(m) => &mut m.unknown_elements,
+            Measurement::Fehler(m) => &mut m.unknown_elements,
+            Measurement::Messwerte(m) => &mut m.unknown_elements,
+            Measurement::ErweiterterFehlerspeicher(m) => &mut m.unknown_elements,
+            Measurement::Unknown(m) => &mut m.unknown_elements,
+        }
+    }
+
+    pub fn get_submeasurement_by_title(&self, title: &str) -> Option<&Measurement> {
+        match self.get_submeasurements() {
+            Some(measurements) => get_measurement_by_title(measurements, title),
+            _ => None,
         }
     }
 }
@@ -396,43 +857,81 @@ impl<'de> Deserialize<'de> for Measurement {
                     "Fehler" => Ok(Measurement::Fehler(MeasurementMistake::from(value))),
                     "Messwerte" => Ok(Measurement::Messwerte(MeasurementMeasuredValues::from(value))),
                     "Erweiterter Fehlerspeicher" => Ok(Measurement::ErweiterterFehlerspeicher(MeasurementExtendedErrorMemory::from(value))),
-                    _ => {
-                        Err(serde::de::Error::custom(format!("'{}' not implemented", value.object.as_str())))
-                    }
+                    _ => Ok(Measurement::Unknown(MeasurementUnknown::from(value))),
                 }
             })
             .deserialize(deserializer)
     }
 }
 
-
-#[derive(Deserialize, Serialize, Debug, Clone)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[derive(Debug, Clone)]
 struct CommonMeasurement {
-    #[serde(rename = "@OBJECT")]
     object: String,
-    #[serde(rename = "TITLE")]
     title: String,
-    #[serde(rename = "VALUE")]
     values: Option<Vec<ValueEnum>>,
-    #[serde(rename = "MEAS")]
     measurements: Option<Vec<Measurement>>,
+    unknown_elements: Vec<RawElement>,
+}
+
+impl<'de> Deserialize<'de> for CommonMeasurement {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct MeasurementVisitor;
+
+        impl<'de> Visitor<'de> for MeasurementVisitor {
+            type Value = CommonMeasurement;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a MEAS element")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let (mut object, mut title, mut values, mut measurements) = (None, None, None, None);
+                let mut unknown_elements = vec![];
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "@OBJECT" => object = Some(map.next_value()?),
+                        "TITLE" => title = Some(map.next_value()?),
+                        "VALUE" => extend(&mut values, map.next_value()?),
+                        "MEAS" => extend(&mut measurements, map.next_value()?),
+                        _ => unknown_elements.push(RawElement { content: map.next_value()?, name: key }),
+                    }
+                }
+                Ok(CommonMeasurement {
+                    object: object.ok_or_else(|| serde::de::Error::missing_field("@OBJECT"))?,
+                    title: title.ok_or_else(|| serde::de::Error::missing_field("TITLE"))?,
+                    values,
+                    measurements,
+                    unknown_elements,
+                })
+            }
+        }
+
+        deserializer.deserialize_map(MeasurementVisitor)
+    }
 }
 
 #[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct MeasurementCoding {
     pub title: String,
     pub values: Option<Vec<ValueEnum>>,
+    pub measurements: Option<Vec<Measurement>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_elements: Vec<RawElement>,
 }
 
 impl From<CommonMeasurement> for MeasurementCoding {
     fn from(m: CommonMeasurement) -> Self {
-        if m.measurements != None {
-            panic!("unexpected measurements for MeasurementCoding");
-        }
         MeasurementCoding {
             title: m.title,
             values: m.values,
+            measurements: m.measurements,
+            unknown_elements: m.unknown_elements,
         }
     }
 }
@@ -441,16 +940,18 @@ impl From<CommonMeasurement> for MeasurementCoding {
 pub struct MeasurementIdentification {
     pub title: String,
     pub values: Option<Vec<ValueEnum>>,
+    pub measurements: Option<Vec<Measurement>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_elements: Vec<RawElement>,
 }
 
 impl From<CommonMeasurement> for MeasurementIdentification {
     fn from(m: CommonMeasurement) -> Self {
-        if m.measurements != None {
-            panic!("unexpected measurements for MeasurementIdentification");
-        }
         MeasurementIdentification {
             title: m.title,
             values: m.values,
+            measurements: m.measurements,
+            unknown_elements: m.unknown_elements,
         }
     }
 }
@@ -459,16 +960,18 @@ impl From<CommonMeasurement> for MeasurementIdentification {
 pub struct MeasurementMeasuredValues {
     pub title: String,
     pub values: Option<Vec<ValueEnum>>,
+    pub measurements: Option<Vec<Measurement>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_elements: Vec<RawElement>,
 }
 
 impl From<CommonMeasurement> for MeasurementMeasuredValues {
     fn from(m: CommonMeasurement) -> Self {
-        if m.measurements != None {
-            panic!("unexpected measurements for MeasurementMeasuredValues");
-        }
         MeasurementMeasuredValues {
             title: m.title,
             values: m.values,
+            measurements: m.measurements,
+            unknown_elements: m.unknown_elements,
         }
     }
 }
@@ -478,6 +981,8 @@ pub struct MeasurementMistake {
     pub title: String,
     pub values: Option<Vec<ValueEnum>>,
     pub measurements: Option<Vec<Measurement>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_elements: Vec<RawElement>,
 }
 
 impl From<CommonMeasurement> for MeasurementMistake {
@@ -486,6 +991,7 @@ impl From<CommonMeasurement> for MeasurementMistake {
             title: m.title,
             values: m.values,
             measurements: m.measurements,
+            unknown_elements: m.unknown_elements,
         }
     }
 }
@@ -494,6 +1000,9 @@ impl From<CommonMeasurement> for MeasurementMistake {
 pub struct MeasurementExtendedErrorMemory {
     pub title: String,
     pub values: Option<Vec<ValueEnum>>,
+    pub measurements: Option<Vec<Measurement>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_elements: Vec<RawElement>,
 }
 
 impl From<CommonMeasurement> for MeasurementExtendedErrorMemory {
@@ -501,22 +1010,59 @@ impl From<CommonMeasurement> for MeasurementExtendedErrorMemory {
         MeasurementExtendedErrorMemory {
             title: m.title,
             values: m.values,
+            measurements: m.measurements,
+            unknown_elements: m.unknown_elements,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MeasurementUnknown {
+    pub object: String,
+    pub title: String,
+    pub values: Option<Vec<ValueEnum>>,
+    pub measurements: Option<Vec<Measurement>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_elements: Vec<RawElement>,
+}
+
+impl From<CommonMeasurement> for MeasurementUnknown {
+    fn from(m: CommonMeasurement) -> Self {
+        MeasurementUnknown {
+            object: m.object,
+            title: m.title,
+            values: m.values,
+            measurements: m.measurements,
+            unknown_elements: m.unknown_elements,
         }
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE", tag = "@FORMAT")]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE", tag = "@FORMAT")]
 pub enum ValueEnum {
     Num(NumberValue),
     Alpha(AlphaValue),
+    /// Value with a `@FORMAT` this crate does not know, kept by lenient loads.
+    #[serde(untagged)]
+    Unknown(UnknownValue),
 }
 
 impl ValueEnum {
+    /// The value's `@FORMAT` type.
+    pub fn get_format(&self) -> &str {
+        match self {
+            ValueEnum::Num(_) => "NUM",
+            ValueEnum::Alpha(_) => "ALPHA",
+            ValueEnum::Unknown(u) => &u.format,
+        }
+    }
+
     pub fn get_label(&self) -> &String {
         match self {
             ValueEnum::Num(n) => &n.label,
             ValueEnum::Alpha(a) => &a.label,
+            ValueEnum::Unknown(u) => &u.label,
         }
     }
 
@@ -524,12 +1070,14 @@ impl ValueEnum {
         match self {
             ValueEnum::Num(n) => &n.text,
             ValueEnum::Alpha(a) => &a.text,
+            ValueEnum::Unknown(u) => &u.text,
         }
     }
 
     pub fn get_unit(&self) -> Option<&String> {
         match self {
             ValueEnum::Num(n) => n.unit.as_ref(),
+            ValueEnum::Unknown(u) => u.unit.as_ref(),
             _ => None,
         }
     }
@@ -538,12 +1086,70 @@ impl ValueEnum {
         match self {
             ValueEnum::Num(n) => Some(&n.value),
             ValueEnum::Alpha(a) => a.value.as_ref(),
+            ValueEnum::Unknown(u) => u.value.as_ref(),
+        }
+    }
+
+    pub fn get_value_mut(&mut self) -> Option<&mut String> {
+        match self {
+            ValueEnum::Num(n) => Some(&mut n.value),
+            ValueEnum::Alpha(a) => a.value.as_mut(),
+            ValueEnum::Unknown(u) => u.value.as_mut(),
         }
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+// Values are read through `CommonValue` rather than an internally tagged enum so
+// that unknown attributes are reported instead of being buffered away.
+impl<'de> Deserialize<'de> for ValueEnum {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        UntaggedEnumVisitor::new()
+            .map(|map| {
+                let value: CommonValue = map.deserialize()?;
+                match value.format.as_str() {
+                    "NUM" => Ok(ValueEnum::Num(NumberValue {
+                        text: value.text,
+                        unit: value.unit,
+                        label: value.label,
+                        value: value.value.ok_or_else(|| serde::de::Error::missing_field("$text"))?,
+                    })),
+                    "ALPHA" => Ok(ValueEnum::Alpha(AlphaValue {
+                        text: value.text,
+                        label: value.label,
+                        value: value.value,
+                    })),
+                    _ => Ok(ValueEnum::Unknown(UnknownValue {
+                        format: value.format,
+                        text: value.text,
+                        label: value.label,
+                        unit: value.unit,
+                        value: value.value,
+                    })),
+                }
+            })
+            .deserialize(deserializer)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct CommonValue {
+    #[serde(rename = "@FORMAT")]
+    format: String,
+    #[serde(rename = "@TEXT")]
+    text: String,
+    #[serde(rename = "@UNIT")]
+    unit: Option<String>,
+    #[serde(rename = "@LABEL")]
+    label: String,
+    #[serde(rename = "$text")]
+    value: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct NumberValue {
     #[serde(rename = "@TEXT")]
     pub text: String,
@@ -555,8 +1161,8 @@ pub struct NumberValue {
     pub value: String,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
-#[serde(deny_unknown_fields, rename_all = "SCREAMING_SNAKE_CASE")]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct AlphaValue {
     #[serde(rename = "@TEXT")]
     pub text: String,
@@ -566,6 +1172,27 @@ pub struct AlphaValue {
     pub value: Option<String>,
 }
 
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct UnknownValue {
+    #[serde(rename = "@FORMAT")]
+    pub format: String,
+    #[serde(rename = "@TEXT")]
+    pub text: String,
+    #[serde(rename = "@LABEL")]
+    pub label: String,
+    #[serde(rename = "@UNIT")]
+    pub unit: Option<String>,
+    #[serde(rename = "$text")]
+    pub value: Option<String>,
+}
+
+/// Loads an in-memory FAP XML fixture the way `LoadOptions::load_xml` would, for the tests of every module.
+#[cfg(test)]
+pub(crate) fn load_test_xml(xml: &str) -> VehicleAnalysisLog {
+    LoadOptions::default().load_xml_reader(xml.as_bytes()).unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -578,8 +1205,8 @@ mod tests {
         assert_eq!(val.result.header.timezone, FixedOffset::west_opt(7 * 3600).unwrap());
 
         let section = &val.get_section_by_title("Gateway (A7.1)").unwrap();
-        let m = &section.get_measurement_by_title(&"Control unit, coding".to_string()).unwrap();
-        let value = m.get_value_by_label(&"Batteriewechsel_Technologie_zwei.Scannercode".to_string()).unwrap();
+        let m = &section.get_measurement_by_title("Control unit, coding").unwrap();
+        let value = m.get_value_by_label("Batteriewechsel_Technologie_zwei.Scannercode").unwrap();
         assert_eq!(m.get_title(), "Control unit, coding");
         assert_eq!(value, &ValueEnum::Alpha(AlphaValue {
             text: "Battery change: Scanner code".to_string(),
@@ -589,9 +1216,9 @@ mod tests {
 
 
         let section = &val.get_section_by_title("Airbag (variant: A2.8)").unwrap();
-        let m = &section.get_measurement_by_title(&"Fault".to_string()).unwrap();
-        let submeasurement = m.get_submeasurement_by_title(&"erweiterter Fehlerspeicher".to_string()).unwrap();
-        let value = submeasurement.get_value_by_label(&"Priority".to_string()).unwrap();
+        let m = &section.get_measurement_by_title("Fault").unwrap();
+        let submeasurement = m.get_submeasurement_by_title("erweiterter Fehlerspeicher").unwrap();
+        let value = submeasurement.get_value_by_label("Priority").unwrap();
         assert_eq!(m.get_title(), "Fault");
         assert_eq!(value, &ValueEnum::Alpha(AlphaValue {
             text: "Hinweis_Prio".to_string(),
@@ -599,4 +1226,186 @@ mod tests {
             value: Some("2".to_string()),
         }));
     }
+
+    fn from_str(xml: &str) -> Result<VehicleAnalysisLog> {
+        LoadOptions::default().load_xml_reader(xml.as_bytes())
+    }
+
+    #[test]
+    fn test_nested_measurements() {
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml").replace(
+            r#"<VALUE FORMAT="ALPHA" TEXT="Guidelines" LABEL="Hilfslinien">active</VALUE>"#,
+            r#"<VALUE FORMAT="ALPHA" TEXT="Guidelines" LABEL="Hilfslinien">active</VALUE>
+        <MEAS OBJECT="Codierung">
+          <TITLE>Variant coding</TITLE>
+          <VALUE FORMAT="ALPHA" TEXT="Camera type" LABEL="Kameratyp">top_view</VALUE>
+        </MEAS>"#);
+        let val = from_str(&xml).unwrap();
+
+        let section = val.get_section_by_title("Rear-view camera").unwrap();
+        let m = section.get_measurement_by_title("Control unit, coding").unwrap();
+        let submeasurement = m.get_submeasurement_by_title("Variant coding").unwrap();
+        assert_eq!(submeasurement.get_value_by_label("Kameratyp").unwrap().get_value().unwrap(), "top_view");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(from_str("<FAP><RESULTSHEADER>"), Err(Error::Xml(_))));
+
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml").replace("<ZIP>94000</ZIP>", "");
+        let Err(Error::Schema { path, .. }) = from_str(&xml) else {
+            panic!("expected schema error");
+        };
+        assert_eq!(path, "RESULTSHEADER.CARDEALER");
+
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml").replace("<TITLE>Control unit, coding</TITLE>", "");
+        let Err(Error::Schema { path, .. }) = from_str(&xml) else {
+            panic!("expected schema error");
+        };
+        assert_eq!(path, "RESULT.SECTION[0].MEAS[1]");
+
+        assert!(matches!(VehicleAnalysisLog::from_zip("tests/data/missing.zip"), Err(Error::Io(_))));
+    }
+
+    // Unknown section, measurement and value types, and unknown elements and
+    // attributes where lenient loads keep them.
+    fn unknown_content(xml: &str) -> String {
+        xml.replace(r#"<SECTION OBJECT="ECU">
+      <TITLE>Rear-view camera</TITLE>"#, r#"<SECTION OBJECT="Bus" ADDRESS="0x6C">
+      <TITLE>Rear-view camera</TITLE>"#)
+            .replace(r#"<MEAS OBJECT="Messwerte">"#, r#"<MEAS OBJECT="Stellglieddiagnose">"#)
+            .replace(r#"UNIT="V">12,41</VALUE>"#, r#"UNIT="V">12,41</VALUE>
+        <GRAPH TYPE="line">
+          <POINT>12,40</POINT>
+          <POINT>12,41</POINT>
+        </GRAPH>"#)
+            .replace(r#"<VALUE FORMAT="ALPHA" TEXT="Hardware version""#, r#"<VALUE FORMAT="BCD" TEXT="Hardware version""#)
+    }
+
+    #[test]
+    fn test_lenient() {
+        let xml = unknown_content(include_str!("../tests/data/FAP_SAMPLE_1.xml"))
+            .replace("<PROTOKOLLTYPE>", "<LOCATION>Bay 3</LOCATION><PROTOKOLLTYPE>")
+            .replace(r#"LABEL="Hilfslinien""#, r#"LABEL="Hilfslinien" ID="7""#);
+
+        let Err(Error::Unsupported(warning)) = from_str(&xml) else {
+            panic!("expected unsupported content");
+        };
+        assert_eq!(warning.path, "RESULT.HEADER.LOCATION");
+
+        let val = LoadOptions::lenient().load_xml_reader(xml.as_bytes()).unwrap();
+        let warnings: Vec<_> = val.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, vec![
+            "RESULT.HEADER.LOCATION: unknown element",
+            "RESULT.SECTION.2.MEAS.0.VALUE.0.@ID: unknown element",
+            "RESULT.SECTION.0.MEAS.0.VALUE.1: unknown value format 'BCD'",
+            "RESULT.SECTION.0.MEAS.2: unknown measurement object 'Stellglieddiagnose'",
+            "RESULT.SECTION.0.MEAS.2.GRAPH: unknown element",
+            "RESULT.SECTION.2: unknown section object 'Bus'",
+            "RESULT.SECTION.2.@ADDRESS: unknown element",
+        ]);
+        let Section::Unknown(section) = val.get_section_by_title("Rear-view camera").unwrap() else {
+            panic!("expected unknown section");
+        };
+        assert_eq!(section.object, "Bus");
+        assert_eq!(section.measurements.len(), 1);
+        assert_eq!(section.unknown_elements, vec![RawElement { name: "@ADDRESS".to_string(), content: RawContent::Text("0x6C".to_string()) }]);
+        let value = val.get("Gateway*/*/Hardware?ersion").unwrap().unwrap();
+        assert_eq!((value.get_format(), value.get_value().unwrap().as_str()), ("BCD", "H05"));
+    }
+
+    #[test]
+    fn test_load_sources() {
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml");
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        zip.start_file("FAP_SAMPLE_1.xml", zip::write::SimpleFileOptions::default()).unwrap();
+        std::io::Write::write_all(&mut zip, xml.as_bytes()).unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        for val in [
+            VehicleAnalysisLog::from_bytes(&zip).unwrap(),
+            VehicleAnalysisLog::from_bytes(xml.as_bytes()).unwrap(),
+            VehicleAnalysisLog::from_zip_reader(Cursor::new(&zip)).unwrap(),
+            VehicleAnalysisLog::from_xml("tests/data/FAP_SAMPLE_1.xml").unwrap(),
+            VehicleAnalysisLog::from_path("tests/data/FAP_SAMPLE_1.xml").unwrap(),
+            VehicleAnalysisLog::from_dir("tests").unwrap(),
+        ] {
+            assert_eq!(val.results_header.vehicle.ident.vin, "WP0ZZZ99ZTS392124");
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        for xml in [include_str!("../tests/data/FAP_SAMPLE_1.xml"), include_str!("../tests/data/FAP_SAMPLE_2.xml")] {
+            assert_eq!(from_str(xml).unwrap().to_xml().unwrap(), xml);
+        }
+
+        let xml = include_str!("../tests/data/FAP_SAMPLE_1.xml");
+        let val = from_str(xml).unwrap();
+        let zip = val.to_zip_writer(Cursor::new(vec![])).unwrap().into_inner();
+        let written = VehicleAnalysisLog::from_bytes(&zip).unwrap();
+        assert_eq!(written.to_xml().unwrap(), xml);
+        assert_eq!(val.file_name(), "FAP_WP0ZZZ99ZTS392124_20240804_132559_23.0.1");
+
+        let empty = xml.replace(">00001234567890</VALUE>", "></VALUE>");
+        let val = from_str(&empty).unwrap();
+        assert_eq!(val.result.sections[0].get_measurements()[0].get_values().unwrap()[3].get_value(), None);
+        assert_eq!(val.to_xml().unwrap(), empty);
+
+        let xml = unknown_content(xml);
+        let val = LoadOptions::lenient().load_xml_reader(xml.as_bytes()).unwrap();
+        assert_eq!(val.to_xml().unwrap(), xml);
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let xml = unknown_content(include_str!("../tests/data/FAP_SAMPLE_1.xml"))
+            .replace(r#"ADDRESS="0x6C">"#, r#"ADDRESS="0x6C &amp; &quot;0x6D&quot;">
+      <NOTE>Left &amp; right <SIDE>&lt;mirrored&gt;</SIDE></NOTE>"#)
+            .replace("<TITLE>Rear-view camera</TITLE>", r#"<TITLE>Rear-view camera &amp; "top view" &lt;360&gt;</TITLE>"#)
+            .replace(r#"<VALUE FORMAT="ALPHA" TEXT="Guidelines" LABEL="Hilfslinien">active</VALUE>"#,
+                r#"<VALUE FORMAT="ALPHA" TEXT="Guidelines &quot;A&amp;B&quot;" LABEL="Hilfslinien">on &amp; &lt;dynamic&gt;</VALUE>
+        <MEAS OBJECT="Codierung">
+          <TITLE>Variant coding</TITLE>
+          <VALUE FORMAT="ALPHA" TEXT="Camera type" LABEL="Kameratyp">top_view</VALUE>
+          <MEAS OBJECT="Codierung">
+            <TITLE>Lens &amp; housing</TITLE>
+            <VALUE FORMAT="ALPHA" TEXT="Lens" LABEL="Objektiv">wide &lt;170°&gt;</VALUE>
+          </MEAS>
+        </MEAS>"#);
+        let val = LoadOptions::lenient().load_xml_reader(xml.as_bytes()).unwrap();
+        let section = val.get_section_by_title(r#"Rear-view camera & "top view" <360>"#).unwrap();
+        let m = section.get_measurement_by_title("Control unit, coding").unwrap();
+        let lens = m.get_submeasurement_by_title("Variant coding").unwrap().get_submeasurement_by_title("Lens & housing").unwrap();
+        assert_eq!(lens.get_value_by_label("Objektiv").unwrap().get_value().unwrap(), "wide <170°>");
+        assert_eq!(section.get_unknown_elements()[0].content, RawContent::Text(r#"0x6C & "0x6D""#.to_string()));
+        assert_eq!(section.get_unknown_elements()[1].name, "NOTE");
+
+        let written = val.to_xml().unwrap();
+        assert!(written.contains("Rear-view camera &amp; \"top view\" &lt;360&gt;"));
+        let reloaded = LoadOptions::lenient().load_xml_reader(written.as_bytes()).unwrap();
+        assert_eq!(serde_json::to_value(&reloaded).unwrap(), serde_json::to_value(&val).unwrap());
+        assert_eq!(reloaded.warnings, val.warnings);
+
+        let zip = val.to_zip_writer(Cursor::new(vec![])).unwrap().into_inner();
+        let reloaded = LoadOptions::lenient().load_zip_reader(Cursor::new(zip)).unwrap();
+        assert_eq!(serde_json::to_value(&reloaded).unwrap(), serde_json::to_value(&val).unwrap());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let val = LoadOptions::lenient().load_xml_reader(unknown_content(include_str!("../tests/data/FAP_SAMPLE_1.xml")).as_bytes()).unwrap();
+        let json = serde_json::to_value(&val.result.sections).unwrap();
+        assert_eq!(json[0]["@OBJECT"], "ECU");
+        assert_eq!(json[0]["MEAS"][0]["Identifikation"]["values"][0], serde_json::json!({
+            "@FORMAT": "ALPHA",
+            "@TEXT": "VW/Audi part number",
+            "@LABEL": "VWTeilenummer",
+            "$text": "9Y0907530AB",
+        }));
+        assert_eq!(json[0]["MEAS"][0]["Identifikation"]["values"][1]["@FORMAT"], "BCD");
+        assert_eq!(json[0]["MEAS"][2]["Unknown"]["object"], "Stellglieddiagnose");
+        assert_eq!(json[2]["@OBJECT"], "Bus");
+        assert_eq!(json[2]["unknown_elements"][0]["name"], "@ADDRESS");
+    }
 }
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use crate::rules::Matcher;
+use crate::{Section, VehicleAnalysisLog};
+
+/// Selects sections by title. Without `include` matchers every section not
+/// excluded is selected.
+#[derive(Debug, Clone, Default)]
+pub struct SectionFilter {
+    pub include: Vec<Matcher>,
+    pub exclude: Vec<Matcher>,
+}
+
+impl SectionFilter {
+    pub fn matches(&self, title: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|m| m.matches(title)))
+            && !self.exclude.iter().any(|m| m.matches(title))
+    }
+}
+
+impl VehicleAnalysisLog {
+    /// Drops every section `filter` does not select.
+    pub fn retain_sections(&mut self, filter: &SectionFilter) {
+        self.result.sections.retain(|section| filter.matches(section.get_title()));
+    }
+}
+
+/// How the sections of two logs are paired up. The strategies are tried in the
+/// order `mapping`, exact title, normalized title, address and identification,
+/// each one pairing only the sections left over by the ones before. The fuzzy
+/// strategies only pair a section whose key no other section left over in either
+/// log shares, and report the others as ambiguous.
+#[derive(Debug, Clone, Default)]
+pub struct SectionMatching {
+    /// Titles in the first log mapped to titles in the second, for control
+    /// units no strategy recognizes.
+    pub mapping: HashMap<String, String>,
+    /// Pairs sections whose titles are equal without their variant or address
+    /// suffix, see [`normalize_title`].
+    pub normalize: bool,
+    /// Pairs sections whose titles carry the same diagnostic address, see
+    /// [`title_address`].
+    pub by_address: bool,
+    /// Pairs sections of control units reporting the same system name or, for
+    /// those without one, part number.
+    pub by_identification: bool,
+    /// Keeps the pairs whose section in the first log, or for a section found
+    /// in the second log only, whose own title the filter selects. Filtering
+    /// after pairing keeps the renamed counterpart of a selected section.
+    pub filter: SectionFilter,
+}
+
+// A parenthesized title suffix that tells variants of the same control unit
+// apart, unlike e.g. `(driver)`.
+enum Suffix<'a> {
+    Variant,
+    Address(&'a str),
+}
+
+// `0x19` or `A7.1`
+fn is_address(s: &str) -> bool {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        return !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit());
+    }
+    let Some(digits) = s.strip_prefix(|c: char| c.is_ascii_uppercase()) else { return false };
+    digits.split('.').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+// Splits a trailing variant or address suffix off `title`.
+fn split_suffix(title: &str) -> Option<(&str, Suffix<'_>)> {
+    let open = title.strip_suffix(')')?.rfind('(')?;
+    let inner = title[open + 1..title.len() - 1].trim();
+    let suffix = if inner.get(..8).is_some_and(|s| s.eq_ignore_ascii_case("variant:")) {
+        Suffix::Variant
+    } else if is_address(inner) {
+        Suffix::Address(inner)
+    } else {
+        return None;
+    };
+    Some((title[..open].trim_end(), suffix))
+}
+
+/// A section title without its trailing variant and address suffixes, e.g.
+/// `Airbag` for `Airbag (variant: A2.8)` and `Gateway` for `Gateway (A7.1)`.
+/// Other suffixes are kept, as in `Door control unit (driver)`.
+pub fn normalize_title(title: &str) -> &str {
+    let mut title = title.trim();
+    while let Some((rest, _)) = split_suffix(title) {
+        title = rest;
+    }
+    title
+}
+
+/// The diagnostic address a section title ends with, e.g. `A7.1` for
+/// `Gateway (A7.1)`, with hexadecimal addresses in lower case.
+pub fn title_address(title: &str) -> Option<String> {
+    let mut title = title.trim();
+    while let Some((rest, suffix)) = split_suffix(title) {
+        if let Suffix::Address(address) = suffix {
+            return Some(match address.get(..2) {
+                Some("0x" | "0X") => address.to_ascii_lowercase(),
+                _ => address.to_string(),
+            });
+        }
+        title = rest;
+    }
+    None
+}
+
+// Key control units are recognized by across titles.
+fn identification_key(section: &Section) -> Option<String> {
+    let identification = section.get_identification()?;
+    identification.system_name.or(identification.part_number).filter(|key| !key.is_empty())
+}
+
+/// Tells whether two items are the same, for [`pair_in_order`].
+pub(crate) type Same<'a, T> = &'a dyn Fn(&T, &T) -> bool;
+
+/// Pairs `items` with `others`. Each pass pairs every item left over with the
+/// first `other` left over it considers the same, so the n-th of several equal
+/// items is paired with the n-th equal `other`. Returns the index of every
+/// item's counterpart and which `others` were paired.
+pub(crate) fn pair_in_order<T>(items: &[T], others: &[T], passes: &[Same<T>]) -> (Vec<Option<usize>>, Vec<bool>) {
+    let mut pairs = vec![None; items.len()];
+    let mut taken = vec![false; others.len()];
+    for same in passes {
+        for (i, item) in items.iter().enumerate() {
+            if pairs[i].is_some() {
+                continue;
+            }
+            if let Some(j) = (0..others.len()).find(|&j| !taken[j] && same(item, &others[j])) {
+                pairs[i] = Some(j);
+                taken[j] = true;
+            }
+        }
+    }
+    (pairs, taken)
+}
+
+/// Keys found several times in `items` or `others`, along with both counts.
+/// Such items cannot be told apart, so pairing them in order is a guess, even
+/// when both counts are equal.
+pub(crate) fn ambiguous_keys<'a, T>(items: &'a [T], others: &'a [T], key: impl Fn(&'a T) -> &'a str) -> Vec<(&'a str, usize, usize)> {
+    let mut keys: Vec<&str> = vec![];
+    for k in items.iter().chain(others).map(&key) {
+        if !keys.contains(&k) {
+            keys.push(k);
+        }
+    }
+    keys.into_iter()
+        .map(|k| (k, items.iter().filter(|i| key(i) == k).count(), others.iter().filter(|o| key(o) == k).count()))
+        .filter(|(_, count1, count2)| *count1.max(count2) > 1)
+        .collect()
+}
+
+/// Two paired sections; either side is `None` for a section found in only one
+/// of the logs.
+pub type SectionPair<'a> = (Option<&'a Section>, Option<&'a Section>);
+
+/// A key several sections left over in either log share, so that a fuzzy
+/// strategy of [`SectionMatching`] left them unpaired, with its count in each log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmbiguousSections {
+    pub key: String,
+    pub count1: usize,
+    pub count2: usize,
+}
+
+impl SectionMatching {
+    /// Pairs the sections of `val1` with those of `val2`, see
+    /// [`SectionMatching::pair_reporting`].
+    pub fn pair<'a>(&self, val1: &'a VehicleAnalysisLog, val2: &'a VehicleAnalysisLog) -> Vec<SectionPair<'a>> {
+        self.pair_reporting(val1, val2).0
+    }
+
+    /// Pairs the sections of `val1` with those of `val2`. Sections are paired at
+    /// most once. The result lists the sections of `val1` in order, followed by
+    /// the sections found in `val2` only, along with the keys that were too
+    /// ambiguous to pair by.
+    pub fn pair_reporting<'a>(&self, val1: &'a VehicleAnalysisLog, val2: &'a VehicleAnalysisLog) -> (Vec<SectionPair<'a>>, Vec<AmbiguousSections>) {
+        let sections1 = &val1.result.sections;
+        let sections2 = &val2.result.sections;
+        let mapped = |a: &Section, b: &Section| self.mapping.get(a.get_title()).is_some_and(|title| title == b.get_title());
+        let same_title = |a: &Section, b: &Section| a.get_title() == b.get_title();
+        let (mut pairs, mut taken) = pair_in_order(sections1, sections2, &[&mapped, &same_title]);
+
+        let mut ambiguous = vec![];
+        if self.normalize {
+            self.pair_by_key(sections1, sections2, &mut pairs, &mut taken, &mut ambiguous, |s| Some(normalize_title(s.get_title()).to_string()));
+        }
+        if self.by_address {
+            self.pair_by_key(sections1, sections2, &mut pairs, &mut taken, &mut ambiguous, |s| title_address(s.get_title()));
+        }
+        if self.by_identification {
+            self.pair_by_key(sections1, sections2, &mut pairs, &mut taken, &mut ambiguous, identification_key);
+        }
+
+        let mut result: Vec<_> = sections1.iter().zip(&pairs)
+            .map(|(section, j)| (Some(section), j.map(|j| &sections2[j])))
+            .collect();
+        result.extend(sections2.iter().zip(&taken).filter(|(_, taken)| !**taken).map(|(other, _)| (None, Some(other))));
+        result.retain(|(section, other)| section.or(*other).is_some_and(|s| self.filter.matches(s.get_title())));
+        (result, ambiguous)
+    }
+
+    // Pairs the sections left over whose key is found once among those of each
+    // log. Keys found several times are reported if one of their sections in
+    // the first log is selected by the filter.
+    fn pair_by_key(&self, sections1: &[Section], sections2: &[Section], pairs: &mut [Option<usize>], taken: &mut [bool],
+                   ambiguous: &mut Vec<AmbiguousSections>, key: impl Fn(&Section) -> Option<String>) {
+        let keys1: Vec<_> = sections1.iter().zip(pairs.iter()).map(|(s, j)| j.is_none().then(|| key(s)).flatten()).collect();
+        let keys2: Vec<_> = sections2.iter().zip(taken.iter()).map(|(s, taken)| (!taken).then(|| key(s)).flatten()).collect();
+        for (i, k) in keys1.iter().enumerate() {
+            let Some(k) = k else { continue };
+            let candidates: Vec<_> = (0..keys2.len()).filter(|&j| keys2[j].as_ref() == Some(k)).collect();
+            let items: Vec<_> = (0..keys1.len()).filter(|&i| keys1[i].as_ref() == Some(k)).collect();
+            match (items.len(), candidates.as_slice()) {
+                (_, []) => (),
+                (1, &[j]) => {
+                    pairs[i] = Some(j);
+                    taken[j] = true;
+                }
+                (count1, _) => {
+                    if items[0] == i && items.iter().any(|&i| self.filter.matches(sections1[i].get_title())) {
+                        ambiguous.push(AmbiguousSections { key: k.clone(), count1, count2: candidates.len() });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_test_xml;
+
+
+    fn titles(pairs: &[SectionPair]) -> Vec<(Option<String>, Option<String>)> {
+        pairs.iter().map(|(a, b)| (a.map(|s| s.get_title().clone()), b.map(|s| s.get_title().clone()))).collect()
+    }
+
+    #[test]
+    fn test_normalize_title() {
+        assert_eq!(normalize_title("Airbag (variant: A2.8)"), "Airbag");
+        assert_eq!(normalize_title("Gateway (A7.1) (0x19)"), "Gateway");
+        assert_eq!(normalize_title("Rear-view camera"), "Rear-view camera");
+        assert_eq!(normalize_title("Door control unit (driver) (Variant: 2)"), "Door control unit (driver)");
+        assert_eq!(normalize_title("Seat (2)"), "Seat (2)");
+
+        assert_eq!(title_address("Gateway (A7.1)").as_deref(), Some("A7.1"));
+        assert_eq!(title_address("Gateway (0X19) (variant: A2)").as_deref(), Some("0x19"));
+        assert_eq!(title_address("Airbag (variant: A2.8)"), None);
+        assert_eq!(title_address("Door control unit (driver)"), None);
+    }
+
+    #[test]
+    fn test_pair() {
+        let val1 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let xml = include_str!("../tests/data/FAP_SAMPLE_2.xml")
+            .replace("Gateway (A7.1)", "Gateway control unit")
+            .replace("Airbag (variant: A2.8)", "Airbag (variant: A3.0)");
+        let val2 = load_test_xml(&xml);
+        let some = |title: &str| Some(title.to_string());
+
+        assert_eq!(titles(&SectionMatching::default().pair(&val1, &val2)), vec![
+            (some("Gateway (A7.1)"), None),
+            (some("Airbag (variant: A2.8)"), None),
+            (some("Rear-view camera"), None),
+            (None, some("Gateway control unit")),
+            (None, some("Airbag (variant: A3.0)")),
+            (None, some("Parking assistant")),
+        ]);
+
+        let matching = SectionMatching { normalize: true, by_identification: true, ..SectionMatching::default() };
+        assert_eq!(titles(&matching.pair(&val1, &val2)), vec![
+            (some("Gateway (A7.1)"), some("Gateway control unit")),
+            (some("Airbag (variant: A2.8)"), some("Airbag (variant: A3.0)")),
+            (some("Rear-view camera"), None),
+            (None, some("Parking assistant")),
+        ]);
+
+        let matching = SectionMatching {
+            mapping: HashMap::from([("Rear-view camera".to_string(), "Parking assistant".to_string())]),
+            ..SectionMatching::default()
+        };
+        assert_eq!(titles(&matching.pair(&val1, &val2))[2], (some("Rear-view camera"), some("Parking assistant")));
+
+        let val2 = load_test_xml(&include_str!("../tests/data/FAP_SAMPLE_2.xml")
+            .replace("Gateway (A7.1)", "Central gateway (A7.1)")
+            .replace("Airbag (variant: A2.8)", "Airbag (variant: A3.0)")
+            .replace("Parking assistant", "Airbag (0x15)"));
+        let matching = SectionMatching { normalize: true, by_address: true, ..SectionMatching::default() };
+        let (pairs, ambiguous) = matching.pair_reporting(&val1, &val2);
+        assert_eq!(titles(&pairs), vec![
+            (some("Gateway (A7.1)"), some("Central gateway (A7.1)")),
+            (some("Airbag (variant: A2.8)"), None),
+            (some("Rear-view camera"), None),
+            (None, some("Airbag (variant: A3.0)")),
+            (None, some("Airbag (0x15)")),
+        ]);
+        assert_eq!(ambiguous, vec![AmbiguousSections { key: "Airbag".to_string(), count1: 1, count2: 2 }]);
+
+        // sections are selected by their title in the first log
+        let matching = SectionMatching {
+            by_address: true,
+            filter: SectionFilter { include: vec!["Gateway (A7.1)".parse().unwrap()], exclude: vec![] },
+            ..SectionMatching::default()
+        };
+        assert_eq!(titles(&matching.pair(&val1, &val2)), vec![(some("Gateway (A7.1)"), some("Central gateway (A7.1)"))]);
+    }
+
+    #[test]
+    fn test_pair_in_order() {
+        let items = ["a", "b", "a", "a"];
+        let others = ["a", "a", "c", "b"];
+        let (pairs, taken) = pair_in_order(&items, &others, &[&|a, b| a == b]);
+        assert_eq!(pairs, vec![Some(0), Some(3), Some(1), None]);
+        assert_eq!(taken, vec![true, true, false, true]);
+        assert_eq!(ambiguous_keys(&items, &others, |s| s), vec![("a", 3, 2)]);
+        assert_eq!(ambiguous_keys(&["a", "b", "a"], &["b", "a", "a"], |s| s), vec![("a", 2, 2)]);
+    }
+
+    #[test]
+    fn test_section_filter() {
+        let mut val = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let titles = |val: &VehicleAnalysisLog| val.result.sections.iter().map(|s| s.get_title().clone()).collect::<Vec<_>>();
+
+        let filter = SectionFilter { include: vec![], exclude: vec!["/^(Gateway|Rear)/".parse().unwrap()] };
+        assert!(filter.matches("Airbag (variant: A2.8)"));
+        assert!(!filter.matches("Gateway (A7.1)"));
+
+        val.retain_sections(&SectionFilter {
+            include: vec!["Airbag*".parse().unwrap(), "Gateway*".parse().unwrap()],
+            exclude: vec!["*A7.1*".parse().unwrap()],
+        });
+        assert_eq!(titles(&val), vec!["Airbag (variant: A2.8)"]);
+    }
+}
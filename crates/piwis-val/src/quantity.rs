@@ -0,0 +1,322 @@
+use std::fmt;
+use serde::{Serialize, Serializer};
+use crate::{NumberValue, UnitString, ValueEnum};
+
+/// Unit of a numeric value, normalized from the spellings control units and
+/// testers report, e.g. `Std`, `hours` and `h` are all [`Unit::Hour`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Kilometer,
+    Mile,
+    Hour,
+    Minute,
+    Second,
+    Volt,
+    Millivolt,
+    Ampere,
+    Milliampere,
+    Celsius,
+    Rpm,
+    Percent,
+    Bar,
+    KilometerPerHour,
+    /// A unit not known here, as written in the log.
+    Other(String),
+}
+
+// Spellings are compared case-insensitively.
+const UNIT_NAMES: &[(&str, Unit)] = &[
+    ("km", Unit::Kilometer),
+    ("mi", Unit::Mile),
+    ("miles", Unit::Mile),
+    ("h", Unit::Hour),
+    ("hours", Unit::Hour),
+    ("std", Unit::Hour),
+    ("min", Unit::Minute),
+    ("s", Unit::Second),
+    ("sec", Unit::Second),
+    ("v", Unit::Volt),
+    ("volt", Unit::Volt),
+    ("mv", Unit::Millivolt),
+    ("a", Unit::Ampere),
+    ("ma", Unit::Milliampere),
+    ("°c", Unit::Celsius),
+    ("degc", Unit::Celsius),
+    ("grad c", Unit::Celsius),
+    ("rpm", Unit::Rpm),
+    ("1/min", Unit::Rpm),
+    ("u/min", Unit::Rpm),
+    ("%", Unit::Percent),
+    ("bar", Unit::Bar),
+    ("km/h", Unit::KilometerPerHour),
+];
+
+impl From<&str> for Unit {
+    fn from(unit: &str) -> Self {
+        let lower = unit.trim().to_lowercase();
+        UNIT_NAMES.iter()
+            .find(|(name, _)| *name == lower)
+            .map(|(_, unit)| unit.clone())
+            .unwrap_or_else(|| Unit::Other(unit.trim().to_string()))
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Unit::Kilometer => "km",
+            Unit::Mile => "mi",
+            Unit::Hour => "h",
+            Unit::Minute => "min",
+            Unit::Second => "s",
+            Unit::Volt => "V",
+            Unit::Millivolt => "mV",
+            Unit::Ampere => "A",
+            Unit::Milliampere => "mA",
+            Unit::Celsius => "°C",
+            Unit::Rpm => "rpm",
+            Unit::Percent => "%",
+            Unit::Bar => "bar",
+            Unit::KilometerPerHour => "km/h",
+            Unit::Other(unit) => unit,
+        })
+    }
+}
+
+impl Serialize for Unit {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Distance,
+    Time,
+    Voltage,
+    Current,
+}
+
+impl Unit {
+    // Dimension and factor to the dimension's base unit.
+    fn scale(&self) -> Option<(Dimension, f64)> {
+        match self {
+            Unit::Kilometer => Some((Dimension::Distance, 1.0)),
+            Unit::Mile => Some((Dimension::Distance, 1.609344)),
+            Unit::Hour => Some((Dimension::Time, 3600.0)),
+            Unit::Minute => Some((Dimension::Time, 60.0)),
+            Unit::Second => Some((Dimension::Time, 1.0)),
+            Unit::Volt => Some((Dimension::Voltage, 1.0)),
+            Unit::Millivolt => Some((Dimension::Voltage, 0.001)),
+            Unit::Ampere => Some((Dimension::Current, 1.0)),
+            Unit::Milliampere => Some((Dimension::Current, 0.001)),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a number as written in logs: with a decimal comma or point, `.`, `,`,
+/// `'` or spaces grouping thousands, or as hex with a `0x` prefix. A lone `.` or
+/// `,` is the decimal separator, so `1,013` bar is 1.013; only a separator
+/// repeated on its own or followed by the other one groups thousands.
+pub fn parse_number(value: &str) -> Option<f64> {
+    parse_separated(value, false)
+}
+
+/// Parses a whole number such as an odometer reading or a counter. Unlike in
+/// [`parse_number`], a lone separator followed by exactly three digits groups
+/// thousands, as in `12.345` km, unless the integer part is `0`.
+pub fn parse_whole_number(value: &str) -> Option<i64> {
+    let number = parse_separated(value, true)?;
+    (number.fract() == 0.0 && number.abs() < i64::MAX as f64).then_some(number as i64)
+}
+
+fn parse_separated(value: &str, lone_groups: bool) -> Option<f64> {
+    let value = value.trim();
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let number = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else {
+        let digits: String = digits.chars().filter(|c| !c.is_whitespace() && *c != '\'').collect();
+        // with both separators the last one is the decimal one; a separator
+        // repeated on its own groups thousands, as in `1.234.567`
+        let separators: Vec<_> = digits.match_indices(['.', ',']).collect();
+        let decimal = match separators.as_slice() {
+            [(i, _)] if lone_groups && digits.len() - i - 1 == 3 && &digits[..*i] != "0" => None,
+            [(i, _)] => Some(*i),
+            [.., (i, s)] if separators.iter().any(|(_, other)| other != s) => Some(*i),
+            _ => None,
+        };
+        let normalized: String = digits.char_indices()
+            .filter_map(|(i, c)| match c {
+                '.' | ',' if Some(i) == decimal => Some('.'),
+                '.' | ',' => None,
+                c => Some(c),
+            })
+            .collect();
+        if normalized.is_empty() || !normalized.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            return None;
+        }
+        normalized.parse().ok()?
+    };
+    Some(if negative { -number } else { number })
+}
+
+/// A number with its unit.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: Option<Unit>,
+}
+
+impl Quantity {
+    /// Parses `value` with the unit from the log's `UNIT` attribute. Without
+    /// one, a unit following the number, as in `12,4 V`, is used.
+    pub fn parse(value: &str, unit: Option<&str>) -> Option<Quantity> {
+        match unit.map(str::trim).filter(|u| !u.is_empty()) {
+            Some(unit) => Some(Quantity { value: parse_number(value)?, unit: Some(unit.into()) }),
+            None => {
+                if let Some(value) = parse_number(value) {
+                    return Some(Quantity { value, unit: None });
+                }
+                let (number, unit) = value.trim().rsplit_once(' ')?;
+                Some(Quantity { value: parse_number(number)?, unit: Some(unit.into()) })
+            }
+        }
+    }
+
+    /// The value as an integer, if it has no fractional part.
+    pub fn as_integer(&self) -> Option<i64> {
+        (self.value.fract() == 0.0 && self.value.abs() < i64::MAX as f64).then_some(self.value as i64)
+    }
+
+    /// Converts to `unit`, `None` unless both units measure the same thing,
+    /// e.g. km to mi or mV to V.
+    pub fn to(&self, unit: Unit) -> Option<Quantity> {
+        if self.unit.as_ref() == Some(&unit) {
+            return Some(self.clone());
+        }
+        let (from, from_scale) = self.unit.as_ref()?.scale()?;
+        let (to, to_scale) = unit.scale()?;
+        (from == to).then(|| Quantity { value: self.value * from_scale / to_scale, unit: Some(unit) })
+    }
+
+    /// Converts to the unit of `other`, so both can be compared. Quantities
+    /// without a unit only compare to each other.
+    pub fn to_unit_of(&self, other: &Quantity) -> Option<Quantity> {
+        match &other.unit {
+            Some(unit) => self.to(unit.clone()),
+            None => self.unit.is_none().then(|| self.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.unit {
+            Some(unit) => write!(f, "{} {}", self.value, unit),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
+
+impl NumberValue {
+    pub fn quantity(&self) -> Option<Quantity> {
+        Quantity::parse(&self.value, self.unit.as_deref())
+    }
+}
+
+impl UnitString {
+    pub fn quantity(&self) -> Option<Quantity> {
+        Quantity::parse(&self.value, Some(&self.unit))
+    }
+}
+
+impl ValueEnum {
+    /// The parsed value of a `NUM` value. `ALPHA` values are not parsed as they
+    /// commonly hold codes that only look numeric, like software versions.
+    pub fn get_quantity(&self) -> Option<Quantity> {
+        match self {
+            ValueEnum::Num(n) => n.quantity(),
+            _ => None,
+        }
+    }
+
+    pub fn get_number(&self) -> Option<f64> {
+        self.get_quantity().map(|q| q.value)
+    }
+
+    pub fn get_integer(&self) -> Option<i64> {
+        self.get_quantity()?.as_integer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_test_xml;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("12,41"), Some(12.41));
+        assert_eq!(parse_number("12.41"), Some(12.41));
+        assert_eq!(parse_number(" -3 "), Some(-3.0));
+        assert_eq!(parse_number("12.345,6"), Some(12345.6));
+        assert_eq!(parse_number("12,345.6"), Some(12345.6));
+        assert_eq!(parse_number("1.234.567"), Some(1234567.0));
+        assert_eq!(parse_number("1,013"), Some(1.013));
+        assert_eq!(parse_number("1.250"), Some(1.25));
+        assert_eq!(parse_number("-1.500"), Some(-1.5));
+        assert_eq!(parse_number("0,125"), Some(0.125));
+        assert_eq!(parse_number("12,3456"), Some(12.3456));
+        assert_eq!(parse_number("12 345"), Some(12345.0));
+        assert_eq!(parse_number("12'345"), Some(12345.0));
+        assert_eq!(parse_number("0x1F"), Some(31.0));
+        assert_eq!(parse_number("0456"), Some(456.0));
+        assert_eq!(parse_number("H05"), None);
+        assert_eq!(parse_number(""), None);
+        assert_eq!(parse_number("1e5"), None);
+    }
+
+    #[test]
+    fn test_parse_whole_number() {
+        assert_eq!(parse_whole_number("12.345"), Some(12345));
+        assert_eq!(parse_whole_number("12,345"), Some(12345));
+        assert_eq!(parse_whole_number("1.234.567"), Some(1234567));
+        assert_eq!(parse_whole_number(" 42 "), Some(42));
+        assert_eq!(parse_whole_number("-3"), Some(-3));
+        assert_eq!(parse_whole_number("0,125"), None);
+        assert_eq!(parse_whole_number("12,5"), None);
+        assert_eq!(parse_whole_number("abc"), None);
+    }
+
+    #[test]
+    fn test_quantity() {
+        let val = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+
+        let data = &val.results_header.vehicle.data;
+        assert_eq!(data.odometer.quantity(), Some(Quantity { value: 12345.0, unit: Some(Unit::Kilometer) }));
+        assert_eq!(data.onboard_voltage.quantity().unwrap().to(Unit::Millivolt).unwrap().value, 12400.0);
+        let miles = data.odometer.quantity().unwrap().to(Unit::Mile).unwrap();
+        assert_eq!(miles.to_string().split_once('.').unwrap().0, "7670");
+        assert_eq!(data.operating_time.quantity().unwrap().to(Unit::Volt), None);
+
+        let voltage = val.get("Gateway*/Measured values/Terminal 30 voltage").unwrap().unwrap();
+        assert_eq!(voltage.get_number(), Some(12.41));
+        assert_eq!(voltage.get_integer(), None);
+        let counter = val.get("Airbag*/**/Haeufigkeitszaehler").unwrap().unwrap();
+        assert_eq!(counter.get_integer(), Some(3));
+        assert_eq!(val.get("Gateway*/*/Softwareversion").unwrap().unwrap().get_quantity(), None);
+
+        assert_eq!(Quantity::parse("12,4 V", None), Some(Quantity { value: 12.4, unit: Some(Unit::Volt) }));
+        assert_eq!(Unit::from("U/min"), Unit::Rpm);
+        assert_eq!(Unit::from("Std").to_string(), "h");
+        assert_eq!(Unit::from("kPa"), Unit::Other("kPa".to_string()));
+    }
+}
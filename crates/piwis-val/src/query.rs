@@ -0,0 +1,235 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::{Error, Measurement, Result, Section, ValueEnum, VehicleAnalysisLog};
+use crate::walk::ValuePath;
+
+/// Glob over a single path segment: `*` matches any run of characters and `?`
+/// a single one. Matching is case-sensitive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern(Vec<Token>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    Any,
+    AnyOne,
+}
+
+impl Pattern {
+    pub fn matches(&self, s: &str) -> bool {
+        let chars: Vec<_> = s.chars().collect();
+        matches(&self.0, &chars)
+    }
+}
+
+fn matches(tokens: &[Token], chars: &[char]) -> bool {
+    match tokens.split_first() {
+        None => chars.is_empty(),
+        Some((Token::Any, rest)) => (0..=chars.len()).any(|i| matches(rest, &chars[i..])),
+        Some((Token::AnyOne, rest)) => !chars.is_empty() && matches(rest, &chars[1..]),
+        Some((Token::Char(c), rest)) => chars.first() == Some(c) && matches(rest, &chars[1..]),
+    }
+}
+
+/// Parses a single segment, where `/` has no special meaning.
+impl FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(pattern: &str) -> Result<Self> {
+        let mut tokens = vec![];
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '\\' => Token::Char(chars.next().ok_or_else(|| Error::InvalidQuery(pattern.to_string(), "trailing '\\'".to_string()))?),
+                '*' => Token::Any,
+                '?' => Token::AnyOne,
+                c => Token::Char(c),
+            });
+        }
+        Ok(Pattern(tokens))
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in &self.0 {
+            match token {
+                Token::Char(c @ ('*' | '?' | '/' | '\\')) => write!(f, "\\{}", c)?,
+                Token::Char(c) => write!(f, "{}", c)?,
+                Token::Any => f.write_str("*")?,
+                Token::AnyOne => f.write_str("?")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Title(Pattern),
+    /// `**`: any number of measurement levels, including none.
+    AnyDepth,
+}
+
+/// A path to values of a log: `section/measurement/.../value`. The section and
+/// measurement segments match titles, the last segment a value's label or text.
+/// Segments are [`Pattern`]s, a `**` measurement segment matches any number of
+/// nested measurements, and `\` escapes `/`, `*`, `?` and itself.
+///
+/// `Gateway*/Control unit, coding/*Scannercode` finds the battery scanner code,
+/// `Airbag*/**/Priority` the priority of every fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    section: Pattern,
+    measurements: Vec<Segment>,
+    value: Pattern,
+}
+
+fn split_segments(query: &str) -> Result<Vec<(Pattern, bool)>> {
+    let mut segments = vec![];
+    let mut tokens = vec![];
+    let mut literal = true;
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c) => tokens.push(Token::Char(c)),
+                None => return Err(Error::InvalidQuery(query.to_string(), "trailing '\\'".to_string())),
+            },
+            '/' => {
+                segments.push((Pattern(std::mem::take(&mut tokens)), std::mem::replace(&mut literal, true)));
+            }
+            '*' => {
+                tokens.push(Token::Any);
+                literal = false;
+            }
+            '?' => {
+                tokens.push(Token::AnyOne);
+                literal = false;
+            }
+            c => tokens.push(Token::Char(c)),
+        }
+    }
+    segments.push((Pattern(tokens), literal));
+    Ok(segments)
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(query: &str) -> Result<Self> {
+        let mut segments = split_segments(query)?;
+        if segments.len() < 3 {
+            return Err(Error::InvalidQuery(query.to_string(), "expected section/measurement/value".to_string()));
+        }
+        let (value, _) = segments.pop().unwrap();
+        let (section, _) = segments.remove(0);
+        let measurements = segments.into_iter()
+            .map(|(pattern, literal)| match pattern.0.as_slice() {
+                [Token::Any, Token::Any] if !literal => Segment::AnyDepth,
+                _ => Segment::Title(pattern),
+            })
+            .collect();
+        Ok(Query { section, measurements, value })
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/", self.section)?;
+        for segment in &self.measurements {
+            match segment {
+                Segment::Title(pattern) => write!(f, "{}/", pattern)?,
+                Segment::AnyDepth => f.write_str("**/")?,
+            }
+        }
+        write!(f, "{}", self.value)
+    }
+}
+
+fn matches_measurements(segments: &[Segment], titles: &[&str]) -> bool {
+    match segments.split_first() {
+        None => titles.is_empty(),
+        Some((Segment::AnyDepth, rest)) => (0..=titles.len()).any(|i| matches_measurements(rest, &titles[i..])),
+        Some((Segment::Title(pattern), rest)) => {
+            titles.first().is_some_and(|title| pattern.matches(title)) && matches_measurements(rest, &titles[1..])
+        }
+    }
+}
+
+/// A value found by [`VehicleAnalysisLog::query`] along with the section and
+/// measurement holding it.
+#[derive(Debug, Clone)]
+pub struct QueryMatch<'a> {
+    pub path: ValuePath<'a>,
+    pub section: &'a Section,
+    pub measurement: &'a Measurement,
+    pub value: &'a ValueEnum,
+}
+
+impl Query {
+    pub fn matches(&self, path: &ValuePath, value: &ValueEnum) -> bool {
+        self.section.matches(path.section)
+            && matches_measurements(&self.measurements, &path.measurements)
+            && (self.value.matches(value.get_label()) || self.value.matches(value.get_text()))
+    }
+}
+
+impl VehicleAnalysisLog {
+    /// Every value matching `query`, in document order.
+    pub fn query(&self, query: &Query) -> Vec<QueryMatch<'_>> {
+        self.values()
+            .filter(|(path, _, _, value)| query.matches(path, value))
+            .map(|(path, section, measurement, value)| QueryMatch { path, section, measurement, value })
+            .collect()
+    }
+
+    /// Every value matching the [`Query`] `path`.
+    pub fn select(&self, path: &str) -> Result<Vec<&ValueEnum>> {
+        Ok(self.query(&path.parse()?).into_iter().map(|m| m.value).collect())
+    }
+
+    /// The first value matching the [`Query`] `path`.
+    pub fn get(&self, path: &str) -> Result<Option<&ValueEnum>> {
+        Ok(self.select(path)?.into_iter().next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_test_xml;
+
+    #[test]
+    fn test_query() {
+        let val = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+
+        let value = val.get("Gateway (A7.1)/Control unit, coding/Batteriewechsel_Technologie_zwei.Scannercode").unwrap().unwrap();
+        assert_eq!(value.get_value().unwrap(), "205 BA24H9F0EGE");
+        let value = val.get("Gateway*/Measured values/Terminal 30 voltage").unwrap().unwrap();
+        assert_eq!(value.get_unit().unwrap(), "V");
+
+        let priorities: Vec<_> = val.select("Airbag*/**/Priority").unwrap().iter().map(|v| v.get_value().unwrap().as_str()).collect();
+        assert_eq!(priorities, vec!["2", "1"]);
+        assert!(val.select("Airbag*/Fault/Priority").unwrap().is_empty());
+        assert_eq!(val.select("Airbag*/Fault/erweiterter Fehlerspeicher/Priority").unwrap().len(), 2);
+        assert_eq!(val.select("*/**/*").unwrap().len(), 16);
+
+        let matches = val.query(&"*/*/Software?ersion".parse().unwrap());
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].section.get_title(), "Gateway (A7.1)");
+        assert_eq!(matches[0].measurement.get_title(), "Control unit, identification");
+        assert_eq!(matches[0].path.measurements, vec!["Control unit, identification"]);
+
+        assert!(matches!(val.select("Gateway*/Scannercode"), Err(Error::InvalidQuery(..))));
+    }
+
+    #[test]
+    fn test_parse() {
+        let query: Query = r"A\/B/\*\*/**/x?\\".parse().unwrap();
+        assert!(query.section.matches("A/B"));
+        assert_eq!(query.measurements, vec![Segment::Title(Pattern("**".chars().map(Token::Char).collect())), Segment::AnyDepth]);
+        assert!(query.value.matches(r"xy\"));
+        assert_eq!(query.to_string(), r"A\/B/\*\*/**/x?\\");
+    }
+}
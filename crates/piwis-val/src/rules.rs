@@ -0,0 +1,331 @@
+use std::fmt;
+use std::str::FromStr;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::diff::{DiffRecord, Drift, ValDiff};
+use crate::faults::{FaultChange, FaultDiffEntry};
+use crate::query::Pattern;
+
+/// Matches a title, label or text, either as a [`Pattern`] glob or, written
+/// between slashes as in `/^Personalized_settings_.*_\d+$/`, as a regex.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "String")]
+pub enum Matcher {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn matches(&self, s: &str) -> bool {
+        match self {
+            Matcher::Glob(pattern) => pattern.matches(s),
+            Matcher::Regex(regex) => regex.is_match(s),
+        }
+    }
+}
+
+impl TryFrom<String> for Matcher {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.strip_prefix('/').and_then(|s| s.strip_suffix('/')) {
+            Some(regex) => Regex::new(regex).map(Matcher::Regex).map_err(|e| e.to_string()),
+            None => s.parse().map(Matcher::Glob).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+impl FromStr for Matcher {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.to_string().try_into()
+    }
+}
+
+/// Selects differences by where they were found. Every matcher given must match;
+/// `measurement` matches any of the measurements leading to the difference. For
+/// faults the first measurement is the DTC number, header changes have an empty
+/// section and the field name as label. Rules read from a file need at least one
+/// matcher and no other keys, so that a typo is not read as a rule matching every
+/// difference.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(try_from = "RuleFields")]
+pub struct Rule {
+    pub section: Option<Matcher>,
+    pub measurement: Option<Matcher>,
+    pub label: Option<Matcher>,
+    pub text: Option<Matcher>,
+}
+
+// The keys of a rule in a rules file, `level` only being allowed in severity
+// rules. `SeverityRule` does not flatten `Rule` as unknown keys would pass.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFields {
+    level: Option<Severity>,
+    section: Option<Matcher>,
+    measurement: Option<Matcher>,
+    label: Option<Matcher>,
+    text: Option<Matcher>,
+}
+
+impl RuleFields {
+    fn into_rule(self) -> Result<Rule, String> {
+        let rule = Rule { section: self.section, measurement: self.measurement, label: self.label, text: self.text };
+        if rule.section.is_none() && rule.measurement.is_none() && rule.label.is_none() && rule.text.is_none() {
+            return Err("a rule needs at least one of section, measurement, label or text".to_string());
+        }
+        Ok(rule)
+    }
+}
+
+impl TryFrom<RuleFields> for Rule {
+    type Error = String;
+
+    fn try_from(fields: RuleFields) -> Result<Self, Self::Error> {
+        if fields.level.is_some() {
+            return Err("level is only allowed in severity rules".to_string());
+        }
+        fields.into_rule()
+    }
+}
+
+// What a rule is matched against.
+struct Subject<'a> {
+    section: &'a str,
+    measurements: &'a [String],
+    label: Option<&'a str>,
+    text: Option<&'a str>,
+}
+
+impl<'a> From<&'a DiffRecord> for Subject<'a> {
+    fn from(record: &'a DiffRecord) -> Self {
+        Subject {
+            section: &record.section,
+            measurements: &record.measurements,
+            label: record.label.as_deref(),
+            text: record.text.as_deref(),
+        }
+    }
+}
+
+impl<'a> From<&'a Drift> for Subject<'a> {
+    fn from(drift: &'a Drift) -> Self {
+        Subject {
+            section: &drift.path.section,
+            measurements: &drift.path.measurements,
+            label: Some(&drift.label),
+            text: Some(&drift.text),
+        }
+    }
+}
+
+// A fault without changes, matched by its DTC number and description.
+impl<'a> From<&'a FaultDiffEntry> for Subject<'a> {
+    fn from(fault: &'a FaultDiffEntry) -> Self {
+        Subject {
+            section: &fault.section,
+            measurements: std::slice::from_ref(&fault.code),
+            label: None,
+            text: fault.description.as_deref(),
+        }
+    }
+}
+
+impl Rule {
+    fn matches(&self, subject: &Subject) -> bool {
+        let matches = |matcher: &Option<Matcher>, s: Option<&str>| match matcher {
+            Some(matcher) => s.is_some_and(|s| matcher.matches(s)),
+            None => true,
+        };
+        matches(&self.section, Some(subject.section))
+            && self.measurement.as_ref().is_none_or(|m| subject.measurements.iter().any(|s| m.matches(s)))
+            && matches(&self.label, subject.label)
+            && matches(&self.text, subject.text)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "RuleFields")]
+pub struct SeverityRule {
+    pub level: Severity,
+    pub rule: Rule,
+}
+
+impl TryFrom<RuleFields> for SeverityRule {
+    type Error = String;
+
+    fn try_from(mut fields: RuleFields) -> Result<Self, Self::Error> {
+        let level = fields.level.take().ok_or_else(|| "a severity rule needs a level".to_string())?;
+        Ok(SeverityRule { level, rule: fields.into_rule()? })
+    }
+}
+
+/// Rules for which differences [`crate::diff::diff`] reports and how severe they
+/// are, usually read from a file:
+///
+/// ```toml
+/// [[ignore]]
+/// label = "/^Personalized_settings_user_account_\\d+/"
+///
+/// [[include]]
+/// section = "Gateway*"
+///
+/// [[severity]]
+/// level = "critical"
+/// text = "Kodierwert:*"
+/// ```
+///
+/// Differences matching an `ignore` rule are dropped. When there are `include`
+/// rules, only differences matching one of them are kept. The severity is the
+/// highest level of all matching `severity` rules.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DiffRules {
+    #[serde(default)]
+    pub ignore: Vec<Rule>,
+    #[serde(default)]
+    pub include: Vec<Rule>,
+    #[serde(default)]
+    pub severity: Vec<SeverityRule>,
+}
+
+impl DiffRules {
+    fn reports_subject(&self, subject: &Subject) -> bool {
+        !self.ignore.iter().any(|rule| rule.matches(subject))
+            && (self.include.is_empty() || self.include.iter().any(|rule| rule.matches(subject)))
+    }
+
+    pub fn reports(&self, record: &DiffRecord) -> bool {
+        self.reports_subject(&record.into())
+    }
+
+    pub fn severity(&self, record: &DiffRecord) -> Option<Severity> {
+        let subject = record.into();
+        self.severity.iter()
+            .filter(|rule| rule.rule.matches(&subject))
+            .map(|rule| rule.level)
+            .max()
+    }
+
+    /// Sets the severity of every record.
+    pub fn tag(&self, records: &mut [DiffRecord]) {
+        for record in records {
+            record.severity = self.severity(record);
+        }
+    }
+
+    /// Drops every difference that is not reported, unchanged faults included,
+    /// and persisting faults whose changes were all dropped.
+    pub(crate) fn retain(&self, val_diff: &mut ValDiff) {
+        val_diff.entries.retain(|entry| self.reports(&entry.into()));
+        val_diff.drift.retain(|drift| self.reports_subject(&drift.into()));
+        val_diff.ambiguous.retain(|ambiguous| self.reports(&DiffRecord::from(ambiguous)));
+        val_diff.header.changes.retain(|change| self.reports(&DiffRecord::from(change)));
+        val_diff.faults.retain_mut(|fault| match &fault.change {
+            FaultChange::Persisting { changes } if changes.is_empty() => self.reports_subject(&(&*fault).into()),
+            FaultChange::Persisting { changes } => {
+                let changes: Vec<_> = changes.iter()
+                    .filter(|change| self.reports(&DiffRecord::from_fault_change(fault, change)))
+                    .cloned()
+                    .collect();
+                let reported = !changes.is_empty();
+                fault.change = FaultChange::Persisting { changes };
+                reported
+            }
+            _ => DiffRecord::from_fault(fault).iter().all(|record| self.reports(record)),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_test_xml;
+    use crate::diff::{diff, DiffConfig, DiffEntry};
+
+
+    fn rules(json: &str) -> DiffRules {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_rules() {
+        let val1 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+        let val2 = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_2.xml"));
+        let config = |rules| DiffConfig { rules, include_mistakes: true, ..DiffConfig::new(true, false, false, true, false) };
+
+        let all = diff(&val1, &val2, &config(DiffRules::default()));
+        assert_eq!(all.entries.len(), 5);
+
+        let changes = diff(&val1, &val2, &config(rules(r#"{
+            "ignore": [{"label": "/^Kodierwert\\.(comfort|horn)_/"}, {"measurement": "Measured*"}]
+        }"#)));
+        assert_eq!(changes.entries.len(), 2);
+        assert!(changes.entries.iter().all(|e| matches!(e, DiffEntry::Added { .. } | DiffEntry::Removed { .. })));
+        assert!(changes.drift.is_empty());
+        assert_eq!(changes.faults.len(), all.faults.len());
+
+        let changes = diff(&val1, &val2, &config(rules(r#"{
+            "include": [{"section": "Airbag*", "measurement": "B200000"}]
+        }"#)));
+        assert!(changes.entries.is_empty());
+        assert_eq!(changes.faults.len(), 1);
+        assert_eq!(changes.faults[0].code, "B200000");
+
+        // a persisting fault goes with its last reported change
+        let changes = diff(&val1, &val2, &config(rules(r#"{"ignore": [{"measurement": "B200000"}]}"#)));
+        assert!(all.faults.iter().any(|f| f.code == "B200000"));
+        assert!(changes.faults.iter().all(|f| f.code != "B200000"));
+
+        // as do unchanged faults
+        assert_eq!(diff(&val1, &val1, &config(DiffRules::default())).faults.len(), 2);
+        let changes = diff(&val1, &val1, &config(rules(r#"{"include": [{"section": "Gateway*"}]}"#)));
+        assert!(changes.faults.is_empty());
+        let changes = diff(&val1, &val1, &config(rules(r#"{"ignore": [{"measurement": "B100100"}]}"#)));
+        assert_eq!(changes.faults.iter().map(|f| f.code.as_str()).collect::<Vec<_>>(), vec!["B200000"]);
+
+        let rules = rules(r#"{
+            "severity": [{"level": "warning", "section": "*"}, {"level": "critical", "text": "Kodierwert:*"}]
+        }"#);
+        let mut records = all.records();
+        rules.tag(&mut records);
+        assert_eq!(records[0].severity, Some(Severity::Critical));
+        assert!(records[1..].iter().all(|r| r.severity >= Some(Severity::Warning)));
+
+        assert!(Matcher::try_from("/(/".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for json in [
+            r#"{"ignore": [{"lable": "Foo*"}]}"#,
+            r#"{"ignore": [{}]}"#,
+            r#"{"include": [{"level": "info", "section": "*"}]}"#,
+            r#"{"severity": [{"section": "*"}]}"#,
+            r#"{"severity": [{"level": "info"}]}"#,
+            r#"{"severity": [{"level": "info", "lable": "*"}]}"#,
+        ] {
+            assert!(serde_json::from_str::<DiffRules>(json).is_err(), "{}", json);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt;
+use std::slice;
+use serde::Serialize;
+use crate::{Measurement, Section, ValueEnum, VehicleAnalysisLog};
+
+/// Location of a value: the section title, the titles of the measurement holding
+/// it and its parents, outermost first, and the value's label.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ValuePath<'a> {
+    pub section: &'a str,
+    pub measurements: Vec<&'a str>,
+    pub label: &'a str,
+}
+
+impl fmt::Display for ValuePath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.section)?;
+        for measurement in &self.measurements {
+            write!(f, " >> {}", measurement)?;
+        }
+        write!(f, " >> {}", self.label)
+    }
+}
+
+/// Whether [`walk`] descends into a section or measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Walk {
+    Continue,
+    /// Skip the values and sub-measurements; the matching `leave_*` call is
+    /// skipped as well.
+    Skip,
+}
+
+/// Callbacks for [`walk`]. Within a measurement the sub-measurements are visited
+/// before the measurement's own values, the order `valrs dump` has always printed
+/// them in. `measurements` holds the measurement being visited and its parents,
+/// outermost first.
+pub trait Visitor<'a> {
+    fn enter_section(&mut self, _section: &'a Section) -> Walk {
+        Walk::Continue
+    }
+
+    fn leave_section(&mut self, _section: &'a Section) {}
+
+    fn enter_measurement(&mut self, _section: &'a Section, _measurements: &[&'a Measurement]) -> Walk {
+        Walk::Continue
+    }
+
+    fn leave_measurement(&mut self, _section: &'a Section, _measurements: &[&'a Measurement]) {}
+
+    fn visit_value(&mut self, _section: &'a Section, _measurements: &[&'a Measurement], _value: &'a ValueEnum) {}
+}
+
+fn walk_measurements<'a, V: Visitor<'a>>(visitor: &mut V, section: &'a Section, chain: &mut Vec<&'a Measurement>, measurements: &'a [Measurement]) {
+    for measurement in measurements {
+        chain.push(measurement);
+        if visitor.enter_measurement(section, chain) == Walk::Continue {
+            if let Some(submeasurements) = measurement.get_submeasurements() {
+                walk_measurements(visitor, section, chain, submeasurements);
+            }
+            for value in measurement.get_values().into_iter().flatten() {
+                visitor.visit_value(section, chain, value);
+            }
+            visitor.leave_measurement(section, chain);
+        }
+        chain.pop();
+    }
+}
+
+/// Visits every section and measurement of the log in document order, and their
+/// values as described on [`Visitor`].
+pub fn walk<'a, V: Visitor<'a>>(val: &'a VehicleAnalysisLog, visitor: &mut V) {
+    for section in val.result.sections.iter() {
+        if visitor.enter_section(section) == Walk::Continue {
+            walk_measurements(visitor, section, &mut vec![], section.get_measurements());
+            visitor.leave_section(section);
+        }
+    }
+}
+
+/// Iterator over every value of a log, see [`VehicleAnalysisLog::values`].
+pub struct Values<'a> {
+    sections: slice::Iter<'a, Section>,
+    section: Option<&'a Section>,
+    // measurement lists being walked, outermost first; `chain` holds the
+    // measurement last taken from each of them, whose values are yielded once
+    // its sub-measurements are done
+    stack: Vec<slice::Iter<'a, Measurement>>,
+    chain: Vec<&'a Measurement>,
+    values: slice::Iter<'a, ValueEnum>,
+}
+
+impl<'a> Iterator for Values<'a> {
+    type Item = (ValuePath<'a>, &'a Section, &'a Measurement, &'a ValueEnum);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let (Some(value), Some(section), Some(measurement)) = (self.values.next(), self.section, self.chain.last()) {
+                let path = ValuePath {
+                    section: section.get_title(),
+                    measurements: self.chain.iter().map(|m| m.get_title().as_str()).collect(),
+                    label: value.get_label(),
+                };
+                return Some((path, section, measurement, value));
+            }
+            match self.stack.last_mut().map(Iterator::next) {
+                Some(Some(measurement)) => {
+                    self.chain.truncate(self.stack.len() - 1);
+                    self.chain.push(measurement);
+                    match measurement.get_submeasurements() {
+                        Some(submeasurements) => self.stack.push(submeasurements.iter()),
+                        None => self.values = measurement.get_values().map(|v| v.iter()).unwrap_or_default(),
+                    }
+                }
+                Some(None) => {
+                    self.stack.pop();
+                    self.chain.truncate(self.stack.len());
+                    if let Some(measurement) = self.chain.last() {
+                        self.values = measurement.get_values().map(|v| v.iter()).unwrap_or_default();
+                    }
+                }
+                None => {
+                    let section = self.sections.next()?;
+                    self.section = Some(section);
+                    self.chain.clear();
+                    self.stack.push(section.get_measurements().iter());
+                }
+            }
+        }
+    }
+}
+
+impl VehicleAnalysisLog {
+    /// Every value of the log, including those of nested sub-measurements, in the
+    /// order of [`walk`].
+    pub fn values(&self) -> Values<'_> {
+        Values {
+            sections: self.result.sections.iter(),
+            section: None,
+            stack: vec![],
+            chain: vec![],
+            values: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_test_xml;
+
+    #[derive(Default)]
+    struct Counter {
+        sections: usize,
+        measurements: usize,
+        values: usize,
+        depth: usize,
+    }
+
+    impl<'a> Visitor<'a> for Counter {
+        fn enter_section(&mut self, section: &'a Section) -> Walk {
+            self.sections += 1;
+            if section.get_title() == "Gateway (A7.1)" { Walk::Skip } else { Walk::Continue }
+        }
+
+        fn enter_measurement(&mut self, _section: &'a Section, measurements: &[&'a Measurement]) -> Walk {
+            self.measurements += 1;
+            self.depth = self.depth.max(measurements.len());
+            Walk::Continue
+        }
+
+        fn visit_value(&mut self, _section: &'a Section, _measurements: &[&'a Measurement], _value: &'a ValueEnum) {
+            self.values += 1;
+        }
+    }
+
+    struct Labels<'a>(Vec<&'a str>);
+
+    impl<'a> Visitor<'a> for Labels<'a> {
+        fn visit_value(&mut self, _section: &'a Section, _measurements: &[&'a Measurement], value: &'a ValueEnum) {
+            self.0.push(value.get_label());
+        }
+    }
+
+    #[test]
+    fn test_values() {
+        let val = load_test_xml(include_str!("../tests/data/FAP_SAMPLE_1.xml"));
+
+        let paths: Vec<_> = val.values()
+            .filter(|(path, ..)| path.section.starts_with("Airbag"))
+            .map(|(path, ..)| path.to_string())
+            .collect();
+        assert_eq!(paths, vec![
+            "Airbag (variant: A2.8) >> Fault >> erweiterter Fehlerspeicher >> Priority",
+            "Airbag (variant: A2.8) >> Fault >> erweiterter Fehlerspeicher >> Haeufigkeitszaehler",
+            "Airbag (variant: A2.8) >> Fault >> Fehlernummer",
+            "Airbag (variant: A2.8) >> Fault >> Fehlertext",
+            "Airbag (variant: A2.8) >> Fault >> erweiterter Fehlerspeicher >> Priority",
+            "Airbag (variant: A2.8) >> Fault >> erweiterter Fehlerspeicher >> Haeufigkeitszaehler",
+            "Airbag (variant: A2.8) >> Fault >> Fehlernummer",
+            "Airbag (variant: A2.8) >> Fault >> Fehlertext",
+        ]);
+        let (_, section, measurement, value) = val.values().last().unwrap();
+        assert_eq!(section.get_title(), "Rear-view camera");
+        assert_eq!(measurement.get_title(), "Control unit, coding");
+        assert_eq!(value.get_label(), "Hilfslinien");
+        assert_eq!(val.values().count(), 16);
+
+        let mut counter = Counter::default();
+        walk(&val, &mut counter);
+        assert_eq!((counter.sections, counter.measurements, counter.values, counter.depth), (3, 5, 9, 2));
+
+        let mut labels = Labels(vec![]);
+        walk(&val, &mut labels);
+        assert_eq!(labels.0, val.values().map(|(path, ..)| path.label).collect::<Vec<_>>());
+    }
+}
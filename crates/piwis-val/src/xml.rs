@@ -0,0 +1,169 @@
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use crate::{Header, Measurement, RawContent, RawElement, ResultsHeader, Section, ValueEnum, VehicleAnalysisLog};
+
+// Borrowed views writing a log in the shape of the FAP XML: the `@OBJECT` and
+// `@FORMAT` types in front of the fields, as they are read, and unknown elements
+// kept by lenient loads after the known ones. The serde shape of the model types
+// is left to their derives.
+
+#[derive(Serialize)]
+struct LogXml<'a> {
+    #[serde(rename = "RESULTSHEADER")]
+    results_header: &'a ResultsHeader,
+    #[serde(rename = "RESULT")]
+    result: ResultXml<'a>,
+}
+
+#[derive(Serialize)]
+struct ResultXml<'a> {
+    #[serde(rename = "@OBJECT")]
+    object: &'a str,
+    #[serde(rename = "@METHOD")]
+    method: &'a str,
+    #[serde(rename = "TITLE")]
+    title: &'a str,
+    #[serde(rename = "HEADER")]
+    header: &'a Header,
+    #[serde(rename = "SECTION")]
+    sections: Vec<SectionXml<'a>>,
+}
+
+struct SectionXml<'a>(&'a Section);
+
+impl Serialize for SectionXml<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let section = self.0;
+        let unknown_elements = section.get_unknown_elements();
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("@OBJECT", section.get_object())?;
+        serialize_raw(&mut map, unknown_elements, true)?;
+        map.serialize_entry("TITLE", section.get_title())?;
+        map.serialize_entry("MEAS", &section.get_measurements().iter().map(MeasurementXml).collect::<Vec<_>>())?;
+        serialize_raw(&mut map, unknown_elements, false)?;
+        map.end()
+    }
+}
+
+struct MeasurementXml<'a>(&'a Measurement);
+
+impl Serialize for MeasurementXml<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let measurement = self.0;
+        let unknown_elements = measurement.get_unknown_elements();
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("@OBJECT", measurement.get_object())?;
+        serialize_raw(&mut map, unknown_elements, true)?;
+        map.serialize_entry("TITLE", measurement.get_title())?;
+        if let Some(values) = measurement.get_values() {
+            map.serialize_entry("VALUE", &values.iter().map(ValueXml::from).collect::<Vec<_>>())?;
+        }
+        if let Some(measurements) = measurement.get_submeasurements() {
+            map.serialize_entry("MEAS", &measurements.iter().map(MeasurementXml).collect::<Vec<_>>())?;
+        }
+        serialize_raw(&mut map, unknown_elements, false)?;
+        map.end()
+    }
+}
+
+#[derive(Serialize)]
+struct ValueXml<'a> {
+    #[serde(rename = "@FORMAT")]
+    format: &'a str,
+    #[serde(rename = "@TEXT")]
+    text: &'a str,
+    #[serde(rename = "@LABEL")]
+    label: &'a str,
+    #[serde(rename = "@UNIT", skip_serializing_if = "Option::is_none")]
+    unit: Option<&'a String>,
+    #[serde(rename = "$text", skip_serializing_if = "Option::is_none")]
+    value: Option<&'a String>,
+}
+
+impl<'a> From<&'a ValueEnum> for ValueXml<'a> {
+    fn from(value: &'a ValueEnum) -> Self {
+        ValueXml {
+            format: value.get_format(),
+            text: value.get_text(),
+            label: value.get_label(),
+            unit: value.get_unit(),
+            value: value.get_value(),
+        }
+    }
+}
+
+// Raw content is written back as it was read: text as is, children as elements
+// or, named `@name`, attributes.
+struct RawXml<'a>(&'a RawContent);
+
+impl Serialize for RawXml<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            RawContent::Text(text) => serializer.serialize_str(text),
+            RawContent::Children(children) => {
+                let mut map = serializer.serialize_map(Some(children.len()))?;
+                serialize_raw(&mut map, children, true)?;
+                serialize_raw(&mut map, children, false)?;
+                map.end()
+            }
+        }
+    }
+}
+
+// Attributes have to be written before any element.
+fn serialize_raw<M: SerializeMap>(map: &mut M, elements: &[RawElement], attributes: bool) -> Result<(), M::Error> {
+    for element in elements.iter().filter(|e| e.name.starts_with('@') == attributes) {
+        map.serialize_entry(&element.name, &RawXml(&element.content))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn to_string(val: &VehicleAnalysisLog) -> Result<String, quick_xml::DeError> {
+    let result = &val.result;
+    let log = LogXml {
+        results_header: &val.results_header,
+        result: ResultXml {
+            object: &result.object,
+            method: &result.method,
+            title: &result.title,
+            header: &result.header,
+            sections: result.sections.iter().map(SectionXml).collect(),
+        },
+    };
+    let xml = quick_xml::se::to_string_with_root("FAP", &log)?;
+    indent(&xml).map_err(|e| e.into())
+}
+
+// The serializer's own indentation moves the text of elements with attributes onto
+// a line of its own, so the compact output is indented by replaying its events.
+// PIWIS writes values without text as `<VALUE ...></VALUE>`, never self-closing.
+fn indent(xml: &str) -> quick_xml::Result<String> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut writer = quick_xml::Writer::new_with_indent(vec![], b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    loop {
+        match reader.read_event()? {
+            Event::Eof => break,
+            Event::Empty(value) if value.name().as_ref() == b"VALUE" => {
+                let end = value.to_end().into_owned();
+                writer.write_event(Event::Start(value))?;
+                writer.write_event(Event::Text(BytesText::new("")))?;
+                writer.write_event(Event::End(end))?;
+            }
+            event => writer.write_event(event)?,
+        }
+    }
+    let mut xml = String::from_utf8(writer.into_inner()).map_err(|e| e.utf8_error())?;
+    xml.push('\n');
+    Ok(xml)
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use anyhow::{bail, Result};
+use piwis_val::anonymize::{AnonymizeOptions, PersonalField, Redaction};
+use crate::load::LoadArgs;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum AttachmentPolicy {
+    /// Write attachments as they are, unredacted
+    Keep,
+    /// Leave attachments out of the written zip
+    Drop,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AnonymizeArgs {
+    input: String,
+    /// Zip to write, named after the first redacted protocol by default
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    load: LoadArgs,
+
+    /// How personal data is redacted: keep, mask, hash or drop
+    #[clap(long, default_value = "mask")]
+    redaction: Redaction,
+    /// Redaction for a single field, e.g. `dealer-city=keep` (repeatable)
+    #[clap(long = "rule", value_name = "FIELD=REDACTION", value_parser = parse_rule)]
+    rules: Vec<(PersonalField, Redaction)>,
+    /// Secret mixed into hashes, random by default; pass the same one to hash
+    /// values the same across runs and keep it secret
+    #[clap(long)]
+    salt: Option<String>,
+    /// Keep the model, model year and plant encoded in the VIN
+    #[clap(long)]
+    keep_vin_info: bool,
+    /// What to do with attachments, which cannot be redacted; required when the
+    /// archive holds any
+    #[clap(long, value_enum)]
+    attachments: Option<AttachmentPolicy>,
+}
+
+fn parse_rule(rule: &str) -> Result<(PersonalField, Redaction), String> {
+    let (field, redaction) = rule.split_once('=').ok_or_else(|| format!("expected FIELD=REDACTION, got '{}'", rule))?;
+    Ok((field.parse()?, redaction.parse()?))
+}
+
+fn random_salt() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+pub fn anonymize(args: &AnonymizeArgs) -> Result<()> {
+    let mut archive = args.load.load_archive(&args.input)?;
+    if let Some(selector) = args.load.protocol() {
+        archive.retain_protocol(selector)?;
+    }
+    if archive.protocols.is_empty() {
+        bail!("{} holds no FAP XML", args.input);
+    }
+    match args.attachments {
+        Some(AttachmentPolicy::Keep) => (),
+        Some(AttachmentPolicy::Drop) => archive.attachments.clear(),
+        None if archive.attachments.is_empty() => (),
+        None => {
+            let names: Vec<_> = archive.attachments.iter().map(|a| a.name.as_str()).collect();
+            bail!("{} holds attachments that cannot be redacted ({}), pass --attachments keep or --attachments drop",
+                  args.input, names.join(", "));
+        }
+    }
+
+    let salt = match &args.salt {
+        Some(salt) => salt.clone(),
+        None => random_salt()?,
+    };
+    archive.anonymize(&AnonymizeOptions {
+        redaction: args.redaction,
+        rules: HashMap::from_iter(args.rules.iter().copied()),
+        salt,
+        keep_vin_info: args.keep_vin_info,
+    });
+
+    let output = args.output.clone()
+        .unwrap_or_else(|| PathBuf::from(format!("{}.zip", archive.protocols[0].log.file_name())));
+    archive.to_zip(&output)?;
+    println!("{}", output.display());
+
+    Ok(())
+}
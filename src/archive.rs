@@ -0,0 +1,24 @@
+use anyhow::Result;
+use crate::load::LoadArgs;
+
+#[derive(clap::Args, Debug)]
+pub struct ArchiveArgs {
+    input: String,
+    #[command(flatten)]
+    load: LoadArgs,
+}
+
+pub fn archive(args: &ArchiveArgs) -> Result<()> {
+    let archive = args.load.load_archive(&args.input)?;
+
+    for (index, protocol) in archive.protocols.iter().enumerate() {
+        let header = &protocol.log.result.header;
+        println!("{} // {} // {} // {} // {}", index, protocol.name, header.protocol_type,
+                 protocol.log.results_header.vehicle.ident.vin, header.start_test);
+    }
+    for attachment in &archive.attachments {
+        println!("attachment // {} // {} bytes", attachment.name, attachment.data.len());
+    }
+
+    Ok(())
+}
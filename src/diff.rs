@@ -1,10 +1,62 @@
-use anyhow::Result;
-use piwis_val::{Measurement, ValueEnum, VehicleAnalysisLog};
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context, Result};
+use piwis_val::diff::{diff as diff_logs, DiffConfig, DiffEntry, DiffItem, DiffPath, DiffRecord, DriftSummary, ItemKind, Tolerance, Tolerances, ValDiff};
+use piwis_val::faults::{FaultChange, FaultDiffEntry};
+use piwis_val::header::HeaderDiff;
+use piwis_val::matching::SectionMatching;
+use piwis_val::quantity::Unit;
+use piwis_val::rules::DiffRules;
+use serde::Serialize;
+use crate::load::{LoadArgs, SectionArgs};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum DiffFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// How sections of both logs are paired, shared by the commands comparing logs.
+#[derive(clap::Args, Debug)]
+pub struct SectionMatchArgs {
+    /// Also pair sections whose titles differ only in their variant or address
+    /// suffix, that have the same address, or whose control units report the
+    /// same system name or part number
+    #[clap(long)]
+    fuzzy_sections: bool,
+    /// Pair the section titled OLD in the first log with NEW in the second
+    #[clap(long, value_name = "OLD=NEW", value_parser = parse_section_mapping)]
+    map_section: Vec<(String, String)>,
+}
+
+fn parse_section_mapping(s: &str) -> Result<(String, String), String> {
+    let (old, new) = s.split_once('=').ok_or_else(|| format!("expected OLD=NEW, got '{}'", s))?;
+    Ok((old.to_string(), new.to_string()))
+}
+
+impl SectionMatchArgs {
+    pub fn matching(&self) -> SectionMatching {
+        SectionMatching {
+            mapping: self.map_section.iter().cloned().collect(),
+            normalize: self.fuzzy_sections,
+            by_address: self.fuzzy_sections,
+            by_identification: self.fuzzy_sections,
+            ..SectionMatching::default()
+        }
+    }
+}
 
 #[derive(clap::Args, Debug)]
 pub struct DiffArgs {
-    zip1: String,
-    zip2: String,
+    input1: String,
+    input2: String,
+    #[command(flatten)]
+    load: LoadArgs,
+    #[command(flatten)]
+    sections: SectionArgs,
+    #[command(flatten)]
+    section_matching: SectionMatchArgs,
 
     #[clap(long)]
     include_values: bool,
@@ -15,32 +67,56 @@ pub struct DiffArgs {
     #[clap(long)]
     include_extended_errors: bool,
     #[clap(long)]
+    include_header: bool,
+    #[clap(long)]
     #[arg(default_value_t = true)]
     include_coding: bool,
+    #[clap(long, value_enum, default_value_t)]
+    format: DiffFormat,
+
+    /// Tolerance for numeric values, absolute (0.05), relative (2%) or both (0.05/2%)
+    #[clap(long)]
+    tolerance: Option<Tolerance>,
+    /// Tolerance for numeric values in a unit, e.g. V=0.05
+    #[clap(long, value_name = "UNIT=TOLERANCE", value_parser = parse_unit_tolerance)]
+    unit_tolerance: Vec<(Unit, Tolerance)>,
+    /// Tolerance for numeric values with a label, e.g. Spannung_Klemme_30=1%
+    #[clap(long, value_name = "LABEL=TOLERANCE", value_parser = parse_label_tolerance)]
+    label_tolerance: Vec<(String, Tolerance)>,
+    /// Print how far numeric values drifted, per unit; JSON output becomes an
+    /// object holding the `records` and the `drift` summary
+    #[clap(long)]
+    drift: bool,
+    /// TOML or YAML file with ignore, include and severity rules
+    #[clap(long)]
+    rules: Option<PathBuf>,
 }
 
-#[derive(Debug, Default)]
-pub struct DiffConfig {
-    pub(crate) include_mistakes: bool,
-    pub(crate) include_values: bool,
-    pub(crate) include_identification: bool,
-    pub(crate) include_coding: bool,
-    pub(crate) include_extended_errors: bool,
+#[derive(Serialize)]
+struct JsonDiff {
+    records: Vec<DiffRecord>,
+    drift: Vec<DriftSummary>,
 }
 
-impl DiffConfig {
-    pub fn new(include_coding: bool, include_mistakes: bool, include_identification: bool, include_values: bool, include_extended_errors: bool) -> DiffConfig {
-        DiffConfig {
-            include_mistakes,
-            include_values,
-            include_identification,
-            include_coding,
-            include_extended_errors,
-        }
-    }
+fn read_rules(path: &Path) -> Result<DiffRules> {
+    let contents = fs::read_to_string(path).with_context(|| format!("failed reading {}", path.display()))?;
+    let rules = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml::from_str(&contents).map_err(anyhow::Error::from),
+        Some("yaml" | "yml") => serde_yaml::from_str(&contents).map_err(anyhow::Error::from),
+        _ => bail!("unknown rules format {}, expected .toml, .yaml or .yml", path.display()),
+    };
+    rules.with_context(|| format!("invalid rules in {}", path.display()))
 }
 
+fn parse_label_tolerance(s: &str) -> Result<(String, Tolerance), String> {
+    let (label, tolerance) = s.split_once('=').ok_or_else(|| format!("expected KEY=TOLERANCE, got '{}'", s))?;
+    Ok((label.to_string(), tolerance.parse()?))
+}
 
+fn parse_unit_tolerance(s: &str) -> Result<(Unit, Tolerance), String> {
+    let (unit, tolerance) = parse_label_tolerance(s)?;
+    Ok((unit.as_str().into(), tolerance))
+}
 
 macro_rules! printp0 {
     ($vec:expr, $($arg:tt)*) => {{
@@ -49,116 +125,241 @@ macro_rules! printp0 {
     }};
 }
 
-fn select_measurement(measurements: &Vec<Measurement>, title: &String) -> Option<Measurement> {
-    for m in measurements {
-        if m.get_title().as_str() == title.as_str() {
-            return Some(m.clone());
-        }
+// Prefixes the path with the severity the rules give the difference.
+fn tagged(rules: &DiffRules, record: &DiffRecord, mut p0: Vec<String>) -> Vec<String> {
+    if let (Some(severity), Some(first)) = (rules.severity(record), p0.first_mut()) {
+        *first = format!("[{}] {}", severity, first);
     }
-    None
+    p0
 }
 
-fn select_value(other_values: &Vec<ValueEnum>, label: &String) -> Option<ValueEnum> {
-    if let Some(other_value) = other_values.iter().find(|v| &v.get_label().as_str() == &label.as_str()) {
-        return Some(other_value.clone());
-    }
-    None
+fn path_of(path: &DiffPath) -> Vec<String> {
+    let mut p0 = vec![path.section.clone()];
+    p0.extend(path.measurements.iter().cloned());
+    p0
 }
 
-fn should_compare(m: &Measurement, cfg: &DiffConfig) -> bool {
-    match m {
-        Measurement::Codierung(_) => cfg.include_coding,
-        Measurement::Identifikation(_) => cfg.include_identification,
-        Measurement::Fehler(_) => cfg.include_mistakes,
-        Measurement::Messwerte(_) => cfg.include_values,
-        Measurement::ErweiterterFehlerspeicher(_) => cfg.include_extended_errors,
+fn format_duration(seconds: i64) -> String {
+    let sign = if seconds < 0 { "-" } else { "" };
+    let seconds = seconds.abs();
+    match (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60, seconds % 60) {
+        (0, 0, 0, s) => format!("{}{}s", sign, s),
+        (0, 0, m, s) => format!("{}{}m {}s", sign, m, s),
+        (0, h, m, s) => format!("{}{}h {}m {}s", sign, h, m, s),
+        (d, h, m, s) => format!("{}{}d {}h {}m {}s", sign, d, h, m, s),
     }
 }
 
-fn print_measurements_diff(p0: &mut Vec<String>, measurements: &Vec<Measurement>, other_measurements: &Vec<Measurement>, diff_config: &DiffConfig) {
-    for measurement in measurements {
-        if !should_compare(&measurement, diff_config) {
-            continue;
+fn print_header_diff(header: &HeaderDiff) {
+    if header.vin_mismatch {
+        printp0!(["Header"], ":: logs are of different vehicles");
+    }
+    for change in &header.changes {
+        let p0 = ["Header", change.field];
+        match change.delta {
+            Some(delta) => printp0!(p0, ":: '{}' -> '{}' ({:+})", change.old, change.new, delta),
+            None => printp0!(p0, ":: '{}' -> '{}'", change.old, change.new),
         }
-        p0.push(measurement.get_title().clone());
-        let Some(other_measurement) = select_measurement(&other_measurements, &measurement.get_title()) else {
-            printp0!(p0, ":: measurement was not found in second VAL");
-            continue;
-        };
+    }
+    if let Some(seconds) = header.time_between_tests {
+        printp0!(["Header", "time between tests"], ":: {}", format_duration(seconds));
+    }
+}
+
+fn print_diff(val_diff: &ValDiff, rules: &DiffRules) {
+    let mut missing_sections1 = vec![];
+    let mut missing_sections2 = vec![];
 
-        match (&measurement.get_submeasurements(), &other_measurement.get_submeasurements()) {
-            (Some(nested_measurements), Some(other_nested_measurements)) =>
-                print_measurements_diff(p0, nested_measurements, other_nested_measurements, diff_config),
-            (Some(_), None) => printp0!(p0, ":: sub-measurements were not found in second VAL"),
-            _ => (),
+    print_header_diff(&val_diff.header);
+
+    for rename in &val_diff.renamed_sections {
+        printp0!([rename.old.as_str()], ":: paired with '{}' in second VAL", rename.new);
+    }
+
+    for entry in &val_diff.entries {
+        let mut p0 = tagged(rules, &entry.into(), path_of(entry.get_path()));
+        match entry {
+            DiffEntry::Removed { path, item: DiffItem::Section } => missing_sections2.push(path.section.clone()),
+            DiffEntry::Removed { item: DiffItem::Measurement, .. } => printp0!(p0, ":: measurement was not found in second VAL"),
+            DiffEntry::Removed { item: DiffItem::Submeasurements, .. } => printp0!(p0, ":: sub-measurements were not found in second VAL"),
+            DiffEntry::Removed { item: DiffItem::Values, .. } => printp0!(p0, ":: values were not found in second VAL"),
+            DiffEntry::Removed { item: DiffItem::Value(value), .. } => {
+                p0.push(value.get_text().clone());
+                printp0!(p0, ":: value was not found in second VAL");
+            }
+            DiffEntry::Added { path, item: DiffItem::Section } => missing_sections1.push(path.section.clone()),
+            DiffEntry::Added { item: DiffItem::Measurement, .. } => printp0!(p0, ":: measurement was not found in first VAL"),
+            DiffEntry::Added { item: DiffItem::Submeasurements, .. } => printp0!(p0, ":: sub-measurements were not found in first VAL"),
+            DiffEntry::Added { item: DiffItem::Values, .. } => printp0!(p0, ":: values were not found in first VAL"),
+            DiffEntry::Added { item: DiffItem::Value(value), .. } => {
+                p0.push(value.get_text().clone());
+                printp0!(p0, ":: value was not found in first VAL");
+            }
+            DiffEntry::Changed { old, new, .. } => {
+                p0.push(old.get_text().clone());
+                printp0!(p0, ":: '{}' -> '{}'",
+                    old.get_value().map_or("<undefined>", String::as_str),
+                    new.get_value().map_or("<undefined>", String::as_str));
+            }
         }
+    }
 
-        print_values_diff(p0, &measurement.get_values(), &other_measurement.get_values());
-        p0.pop();
+    print_faults_diff(&val_diff.faults, rules);
+
+    if !missing_sections1.is_empty() {
+        println!("Missing section(s) in first VAL: {}", missing_sections1.join(","));
+    }
+    if !missing_sections2.is_empty() {
+        println!("Missing section(s) in second VAL: {}", missing_sections2.join(","));
     }
 }
 
-fn print_values_diff(p0: &mut Vec<String>, values: &Option<&Vec<ValueEnum>>, other_values: &Option<&Vec<ValueEnum>>) {
-    if !values.is_none() && other_values.is_none() {
-        printp0!(p0, ":: values were not found in second VAL");
-        return;
+// Rounds away floating point noise like 0.019999999999999574.
+fn round(value: f64) -> f64 {
+    (value * 1e6).round() / 1e6
+}
+
+fn print_drift_summary(val_diff: &ValDiff) {
+    for summary in val_diff.drift_summary() {
+        let (name, unit) = match &summary.unit {
+            Some(unit) => (unit.to_string(), format!(" {}", unit)),
+            None => ("<no unit>".to_string(), String::new()),
+        };
+        let relative = summary.max_relative.map(|r| format!(" ({:.2}%)", r * 100.0)).unwrap_or_default();
+        printp0!(["Drift", name.as_str()], ":: {} value(s), {} within tolerance, max {}{}{}, mean {}{}",
+            summary.count, summary.within_tolerance, round(summary.max_delta), unit, relative, round(summary.mean_delta), unit);
     }
-    match (&values, &other_values) {
-         (Some(values), Some(other_values)) => {
-            for value in *values {
-                p0.push(value.get_text().clone());
-                let Some(other_value) = select_value(other_values, &value.get_label()) else {
-                    printp0!(p0, ":: value was not found in second VAL");
-                    p0.pop();
-                    continue;
-                };
-                if value.get_value() != other_value.get_value() {
+}
+
+// Goes to stderr so the JSON output stays parseable.
+fn print_ambiguous(val_diff: &ValDiff) {
+    for ambiguous in &val_diff.ambiguous {
+        if ambiguous.item == ItemKind::Section {
+            eprintln!("warning: {} :: shared by {} section(s) in first VAL and {} in second VAL, left unpaired",
+                ambiguous.key, ambiguous.count1, ambiguous.count2);
+            continue;
+        }
+        let mut p0 = path_of(&ambiguous.path);
+        p0.push(match ambiguous.item {
+            ItemKind::Fault => format!("Fault {}", ambiguous.key),
+            _ => ambiguous.key.clone(),
+        });
+        eprintln!("warning: {} :: found {} time(s) in first VAL and {} in second VAL, paired in order",
+            p0.join(" // "), ambiguous.count1, ambiguous.count2);
+    }
+}
+
+fn print_faults_diff(faults: &[FaultDiffEntry], rules: &DiffRules) {
+    for fault in faults {
+        let p0 = vec![fault.section.clone(), match &fault.description {
+            Some(description) => format!("Fault {} {}", fault.code, description),
+            None => format!("Fault {}", fault.code),
+        }];
+        match &fault.change {
+            FaultChange::Appeared => printp0!(tagged(rules, &DiffRecord::from_fault(fault)[0], p0), ":: new in second VAL"),
+            FaultChange::Cleared => printp0!(tagged(rules, &DiffRecord::from_fault(fault)[0], p0), ":: cleared in second VAL"),
+            // unchanged faults have no records either
+            FaultChange::Persisting { changes } if changes.is_empty() => (),
+            FaultChange::Persisting { changes } => {
+                printp0!(p0, ":: persisting");
+                for change in changes {
+                    let mut p0 = tagged(rules, &DiffRecord::from_fault_change(fault, change), p0.clone());
+                    p0.extend(change.measurements.iter().cloned());
+                    p0.push(change.old.as_ref().or(change.new.as_ref()).map(|v| v.get_text().clone()).unwrap_or_default());
                     printp0!(p0, ":: '{}' -> '{}'",
-                    value.get_value().unwrap_or(&"<undefined>".to_string()),
-                    other_value.get_value().unwrap_or(&"<undefined>".to_string()));
+                        change.old.as_ref().and_then(|v| v.get_value()).map_or("<undefined>", String::as_str),
+                        change.new.as_ref().and_then(|v| v.get_value()).map_or("<undefined>", String::as_str));
                 }
-                p0.pop();
             }
         }
-        (Some(_), None) => {
-            printp0!(p0, ":: values were not found in second VAL");
-            return;
-        }
-        (None, Some(_)) => {
-            printp0!(p0, ":: values were not found in first VAL");
-            return;
-        }
-        _ => (),
     }
 }
 
 pub fn diff(args: &DiffArgs) -> Result<()> {
-    let val1 = &VehicleAnalysisLog::from_zip(&args.zip1)?;
-    let val2 = &VehicleAnalysisLog::from_zip(&args.zip2)?;
-
-    let diff_config = &mut DiffConfig::new(args.include_coding,
-                                           args.include_mistakes,
-                                           args.include_identification,
-                                           args.include_values,
-                                           args.include_extended_errors);
-    let mut missing_sections2 = vec![];
+    let val1 = &args.load.load(&args.input1)?;
+    let val2 = &args.load.load(&args.input2)?;
 
-    let mut p0 = vec![];
-    for section in val1.result.sections.iter() {
-        p0.push(section.get_title().clone());
-        let Some(other_section) = val2.get_section_by_title(&section.get_title()) else {
-            missing_sections2.push(section.get_title().clone());
-            p0.pop();
-            continue;
-        };
-        print_measurements_diff(&mut p0, &section.get_measurements(), &other_section.get_measurements(), diff_config);
-        p0.pop();
-    }
+    let diff_config = &DiffConfig {
+        include_header: args.include_header,
+        tolerances: Tolerances {
+            default: args.tolerance,
+            units: args.unit_tolerance.iter().cloned().collect(),
+            labels: args.label_tolerance.iter().cloned().collect(),
+        },
+        rules: args.rules.as_deref().map(read_rules).transpose()?.unwrap_or_default(),
+        // sections are selected by their title in the first log once paired
+        section_matching: SectionMatching { filter: args.sections.filter(), ..args.section_matching.matching() },
+        ..DiffConfig::new(args.include_coding,
+                          args.include_mistakes,
+                          args.include_identification,
+                          args.include_values,
+                          args.include_extended_errors)
+    };
 
-    if missing_sections2.len() > 0 {
-        println!("Missing section(s) in second VAL: {}", missing_sections2.join(","));
+    let val_diff = diff_logs(val1, val2, diff_config);
+    print_ambiguous(&val_diff);
+    match args.format {
+        DiffFormat::Text => {
+            print_diff(&val_diff, &diff_config.rules);
+            if args.drift {
+                print_drift_summary(&val_diff);
+            }
+        }
+        DiffFormat::Json => {
+            let mut records = val_diff.records();
+            diff_config.rules.tag(&mut records);
+            if args.drift {
+                println!("{}", serde_json::to_string_pretty(&JsonDiff { records, drift: val_diff.drift_summary() })?);
+            } else {
+                println!("{}", serde_json::to_string_pretty(&records)?);
+            }
+        }
     }
 
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(name: &str, contents: &str) -> Result<DiffRules> {
+        let path = std::env::temp_dir().join(format!("valrs-{}-{}", std::process::id(), name));
+        fs::write(&path, contents)?;
+        let rules = read_rules(&path);
+        fs::remove_file(&path)?;
+        rules
+    }
+
+    #[test]
+    fn test_read_rules() {
+        let rules = read("rules.toml", r#"
+[[ignore]]
+label = "/^Personalized_settings_user_account_\\d+/"
+
+[[include]]
+section = "Gateway*"
+
+[[severity]]
+level = "critical"
+text = "Kodierwert:*"
+"#).unwrap();
+        assert_eq!((rules.ignore.len(), rules.include.len(), rules.severity.len()), (1, 1, 1));
+        assert!(rules.ignore[0].label.as_ref().unwrap().matches("Personalized_settings_user_account_2"));
+
+        let rules = read("rules.yaml", r#"
+ignore:
+  - measurement: "Measured*"
+severity:
+  - level: warning
+    section: "Airbag*"
+"#).unwrap();
+        assert_eq!((rules.ignore.len(), rules.include.len(), rules.severity.len()), (1, 0, 1));
+
+        // a misspelled key must not become a rule ignoring every difference
+        assert!(read("typo.toml", "[[ignore]]\nlable = \"Foo*\"\n").is_err());
+        assert!(read("typo.yml", "severity:\n  - level: info\n    lable: Foo*\n").is_err());
+        assert!(read("empty.yaml", "ignore:\n  - {}\n").is_err());
+        assert!(read("rules.json", "{}").is_err());
+    }
+}
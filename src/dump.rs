@@ -1,41 +1,193 @@
+use std::io::Write;
 use anyhow::Result;
-use piwis_val::{Measurement, VehicleAnalysisLog};
+use piwis_val::VehicleAnalysisLog;
+use serde::Serialize;
+use crate::load::{LoadArgs, SectionArgs};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum DumpFormat {
+    /// `section >> measurement >> value: value` lines
+    #[default]
+    Text,
+    /// The whole parsed log
+    Json,
+    /// The whole parsed log
+    Yaml,
+    /// One JSON object per value
+    Ndjson,
+    /// One row per value
+    Csv,
+}
 
 #[derive(clap::Args, Debug)]
 pub struct DumpArgs {
-    zip: String,
+    input: String,
+    #[command(flatten)]
+    load: LoadArgs,
+    #[command(flatten)]
+    sections: SectionArgs,
+
+    /// Print the vehicle, dealer and tester data instead of the measurements
+    #[clap(long)]
+    header: bool,
+    #[clap(long, value_enum, default_value_t)]
+    format: DumpFormat,
 }
 
-fn print_measurement(p0: &mut Vec<String>, m: &Measurement) {
-    if let Some(values) = &m.get_values() {
-        for value in *values {
-            p0.push(value.get_text().clone());
-            println!("{}: {}", p0.join(" >> "), value.get_value().unwrap_or(&"undefined".to_string()));
-            p0.pop();
-        }
+/// A value with the section and measurement titles leading to it.
+#[derive(Serialize, Debug)]
+struct DumpRow {
+    section: String,
+    measurements: String,
+    label: String,
+    text: String,
+    unit: Option<String>,
+    format: String,
+    value: Option<String>,
+}
+
+const MEASUREMENT_SEPARATOR: &str = " >> ";
+
+fn collect_rows(val: &VehicleAnalysisLog) -> Vec<DumpRow> {
+    val.values()
+        .map(|(path, _, _, value)| DumpRow {
+            section: path.section.to_string(),
+            measurements: path.measurements.join(MEASUREMENT_SEPARATOR),
+            label: value.get_label().clone(),
+            text: value.get_text().clone(),
+            unit: value.get_unit().cloned(),
+            format: value.get_format().to_string(),
+            value: value.get_value().cloned(),
+        })
+        .collect()
+}
+
+fn write_text<W: Write>(mut out: W, rows: &[DumpRow]) -> Result<()> {
+    for row in rows {
+        writeln!(out, "{}{}{}{}{}: {}", row.section, MEASUREMENT_SEPARATOR, row.measurements, MEASUREMENT_SEPARATOR, row.text,
+                 row.value.as_deref().unwrap_or("undefined"))?;
     }
+    Ok(())
+}
+
+fn write_ndjson<W: Write, T: Serialize>(mut out: W, rows: &[T]) -> Result<()> {
+    for row in rows {
+        serde_json::to_writer(&mut out, row)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_csv<W: Write, T: Serialize>(out: W, rows: &[T]) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
 }
 
-fn print_measurements(p0: &mut Vec<String>, m: &Vec<Measurement>) {
-    for measurement in m {
-        p0.push(measurement.get_title().clone());
-        if let Some(submeasurements) = measurement.get_submeasurements() {
-            print_measurements(p0, submeasurements);
+fn write_json<W: Write, T: Serialize + ?Sized>(mut out: W, value: &T) -> Result<()> {
+    serde_json::to_writer_pretty(&mut out, value)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+// Writes the header fields or the values of `val` in `format`.
+fn write_dump<W: Write>(mut out: W, val: &VehicleAnalysisLog, format: DumpFormat, header: bool) -> Result<()> {
+    if header {
+        let fields = val.header_fields();
+        match format {
+            DumpFormat::Text => fields.iter().try_for_each(|f| writeln!(out, "{}: {}", f.field, f.value))?,
+            DumpFormat::Json => write_json(out, &fields)?,
+            DumpFormat::Yaml => serde_yaml::to_writer(out, &fields)?,
+            DumpFormat::Ndjson => write_ndjson(out, &fields)?,
+            DumpFormat::Csv => write_csv(out, &fields)?,
         }
-        print_measurement(p0, measurement);
-        p0.pop();
+        return Ok(());
+    }
+
+    match format {
+        DumpFormat::Text => write_text(out, &collect_rows(val))?,
+        DumpFormat::Json => write_json(out, val)?,
+        DumpFormat::Yaml => serde_yaml::to_writer(out, val)?,
+        DumpFormat::Ndjson => write_ndjson(out, &collect_rows(val))?,
+        DumpFormat::Csv => write_csv(out, &collect_rows(val))?,
     }
+    Ok(())
 }
 
 pub fn dump(args: &DumpArgs) -> Result<()> {
-    let val = &VehicleAnalysisLog::from_zip(&args.zip)?;
+    let val = &mut args.load.load(&args.input)?;
+    val.retain_sections(&args.sections.filter());
+    write_dump(std::io::stdout().lock(), val, args.format, args.header)
+}
 
-    let mut p0 = vec![];
-    for section in val.result.sections.iter() {
-        p0.push(section.get_title().clone());
-        print_measurements(&mut p0, &section.get_measurements());
-        p0.pop();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use piwis_val::LoadOptions;
+
+    fn dump(format: DumpFormat, header: bool) -> String {
+        let val = LoadOptions::default().load_xml_reader(include_str!("../crates/piwis-val/tests/data/FAP_SAMPLE_1.xml").as_bytes()).unwrap();
+        let mut out = vec![];
+        write_dump(&mut out, &val, format, header).unwrap();
+        String::from_utf8(out).unwrap()
     }
 
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn test_dump() {
+        let text = dump(DumpFormat::Text, false);
+        assert_eq!(text.lines().next(), Some("Gateway (A7.1) >> Control unit, identification >> VW/Audi part number: 9Y0907530AB"));
+
+        // titles with commas are quoted
+        let csv = dump(DumpFormat::Csv, false);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "section,measurements,label,text,unit,format,value");
+        assert_eq!(lines[1], "Gateway (A7.1),\"Control unit, identification\",VWTeilenummer,VW/Audi part number,,ALPHA,9Y0907530AB");
+        assert_eq!(lines.len(), text.lines().count() + 1);
+
+        let ndjson = dump(DumpFormat::Ndjson, false);
+        let row: serde_json::Value = serde_json::from_str(ndjson.lines().next().unwrap()).unwrap();
+        assert_eq!(row, serde_json::json!({
+            "section": "Gateway (A7.1)",
+            "measurements": "Control unit, identification",
+            "label": "VWTeilenummer",
+            "text": "VW/Audi part number",
+            "unit": null,
+            "format": "ALPHA",
+            "value": "9Y0907530AB",
+        }));
+
+        // the whole log, in the shape of the XML
+        let json: serde_json::Value = serde_json::from_str(&dump(DumpFormat::Json, false)).unwrap();
+        assert_eq!(json["RESULTSHEADER"]["VEHICLE"]["IDENT"]["VIN"], "WP0ZZZ99ZTS392124");
+        let section = &json["RESULT"]["SECTION"][0];
+        assert_eq!((&section["@OBJECT"], &section["TITLE"]), (&"ECU".into(), &"Gateway (A7.1)".into()));
+        let identification = &section["MEAS"][0]["Identifikation"];
+        assert_eq!(identification["title"], "Control unit, identification");
+        assert_eq!(identification["values"][0], serde_json::json!({
+            "@FORMAT": "ALPHA",
+            "@TEXT": "VW/Audi part number",
+            "@LABEL": "VWTeilenummer",
+            "$text": "9Y0907530AB",
+        }));
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&dump(DumpFormat::Yaml, false)).unwrap();
+        assert_eq!(yaml["RESULTSHEADER"]["VEHICLE"]["IDENT"]["VIN"], "WP0ZZZ99ZTS392124");
+        assert_eq!(yaml["RESULT"]["SECTION"][1]["TITLE"], "Airbag (variant: A2.8)");
+    }
+
+    #[test]
+    fn test_dump_header() {
+        let text = dump(DumpFormat::Text, true);
+        assert!(text.lines().any(|line| line == "vin: WP0ZZZ99ZTS392124"));
+        assert!(text.lines().any(|line| line == "odometer: 12345 km"));
+
+        let json: serde_json::Value = serde_json::from_str(&dump(DumpFormat::Json, true)).unwrap();
+        assert_eq!(json[0], serde_json::json!({"field": "regulation", "value": "USA"}));
+
+        let csv = dump(DumpFormat::Csv, true);
+        assert!(csv.lines().any(|line| line == "dealer.address,1 Main Street"));
+        assert!(csv.starts_with("field,value\n"));
+    }
+}
@@ -0,0 +1,258 @@
+use std::path::PathBuf;
+use anyhow::Result;
+use piwis_val::{Measurement, VehicleAnalysisLog};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use crate::load::LoadArgs;
+
+#[derive(clap::Args, Debug)]
+pub struct ExportArgs {
+    #[clap(required = true)]
+    inputs: Vec<String>,
+    #[command(flatten)]
+    load: LoadArgs,
+
+    /// SQLite database to append the logs to, created if missing
+    #[clap(long)]
+    sqlite: PathBuf,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS logs (
+    id INTEGER PRIMARY KEY,
+    file_name TEXT NOT NULL,
+    vin TEXT NOT NULL,
+    registration TEXT NOT NULL,
+    odometer TEXT NOT NULL,
+    odometer_unit TEXT NOT NULL,
+    operating_time TEXT NOT NULL,
+    operating_time_unit TEXT NOT NULL,
+    order_type TEXT NOT NULL,
+    model TEXT,
+    model_type TEXT NOT NULL,
+    engine_type TEXT NOT NULL,
+    country_code TEXT NOT NULL,
+    gearbox_type TEXT NOT NULL,
+    onboard_voltage TEXT NOT NULL,
+    onboard_voltage_unit TEXT NOT NULL,
+    regulation TEXT NOT NULL,
+    language TEXT NOT NULL,
+    dealer_name TEXT NOT NULL,
+    dealer_company TEXT NOT NULL,
+    dealer_address TEXT NOT NULL,
+    dealer_zip TEXT NOT NULL,
+    dealer_city TEXT NOT NULL,
+    dealer_tel TEXT NOT NULL,
+    dealer_no TEXT NOT NULL,
+    dealer_order TEXT NOT NULL,
+    warranty_no TEXT NOT NULL,
+    title TEXT NOT NULL,
+    protocol_type TEXT NOT NULL,
+    start_test TEXT NOT NULL,
+    end_test TEXT NOT NULL,
+    timezone TEXT NOT NULL,
+    tester_type TEXT NOT NULL,
+    tester_title TEXT NOT NULL,
+    tester_manufacturer TEXT NOT NULL,
+    tester_model TEXT NOT NULL,
+    tester_serial_no TEXT NOT NULL,
+    tester_firmware TEXT NOT NULL,
+    tester_version TEXT NOT NULL,
+    pt2g_version TEXT NOT NULL,
+    br_pdx TEXT NOT NULL,
+    pdu_api TEXT NOT NULL,
+    samdiax_version TEXT NOT NULL,
+    tester_system TEXT NOT NULL,
+    tester_java TEXT NOT NULL,
+    tester_mode TEXT NOT NULL,
+    UNIQUE (vin, protocol_type, start_test, end_test)
+);
+CREATE TABLE IF NOT EXISTS sections (
+    id INTEGER PRIMARY KEY,
+    log_id INTEGER NOT NULL REFERENCES logs(id),
+    position INTEGER NOT NULL,
+    object TEXT NOT NULL,
+    title TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS measurements (
+    id INTEGER PRIMARY KEY,
+    log_id INTEGER NOT NULL REFERENCES logs(id),
+    section_id INTEGER NOT NULL REFERENCES sections(id),
+    parent_id INTEGER REFERENCES measurements(id),
+    position INTEGER NOT NULL,
+    object TEXT NOT NULL,
+    title TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS measurement_values (
+    id INTEGER PRIMARY KEY,
+    log_id INTEGER NOT NULL REFERENCES logs(id),
+    measurement_id INTEGER NOT NULL REFERENCES measurements(id),
+    position INTEGER NOT NULL,
+    format TEXT NOT NULL,
+    label TEXT NOT NULL,
+    text TEXT NOT NULL,
+    unit TEXT,
+    value TEXT
+);
+CREATE INDEX IF NOT EXISTS sections_log_id ON sections(log_id);
+CREATE INDEX IF NOT EXISTS measurements_section_id ON measurements(section_id);
+CREATE INDEX IF NOT EXISTS measurement_values_measurement_id ON measurement_values(measurement_id);
+";
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn insert_log(tx: &Transaction, val: &VehicleAnalysisLog) -> Result<i64> {
+    let dealer = &val.results_header.car_dealer;
+    let vehicle = &val.results_header.vehicle;
+    let header = &val.result.header;
+    let equipment = &header.equipment;
+    tx.execute(
+        "INSERT INTO logs (
+            file_name, vin, registration, odometer, odometer_unit, operating_time, operating_time_unit,
+            order_type, model, model_type, engine_type, country_code, gearbox_type, onboard_voltage,
+            onboard_voltage_unit, regulation, language, dealer_name, dealer_company, dealer_address,
+            dealer_zip, dealer_city, dealer_tel, dealer_no, dealer_order, warranty_no, title,
+            protocol_type, start_test, end_test, timezone, tester_type, tester_title, tester_manufacturer,
+            tester_model, tester_serial_no, tester_firmware, tester_version, pt2g_version, br_pdx,
+            pdu_api, samdiax_version, tester_system, tester_java, tester_mode
+        ) VALUES (
+            ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20,
+            ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34, ?35, ?36, ?37, ?38,
+            ?39, ?40, ?41, ?42, ?43, ?44, ?45
+        )",
+        params![
+            val.file_name(), vehicle.ident.vin, vehicle.ident.registration,
+            vehicle.data.odometer.value, vehicle.data.odometer.unit,
+            vehicle.data.operating_time.value, vehicle.data.operating_time.unit,
+            vehicle.data.order_type, vehicle.data.model, vehicle.data.model_type, vehicle.data.engine_type,
+            vehicle.data.country_code, vehicle.data.gearbox_type,
+            vehicle.data.onboard_voltage.value, vehicle.data.onboard_voltage.unit,
+            val.results_header.country.regulation, val.results_header.country.language,
+            dealer.name, dealer.company, dealer.address, dealer.zip, dealer.city, dealer.tel,
+            dealer.dealer_no, dealer.order, dealer.warranty_no,
+            val.result.title, header.protocol_type,
+            header.start_test.format(TIME_FORMAT).to_string(), header.end_test.format(TIME_FORMAT).to_string(),
+            format!("GMT{}", header.timezone),
+            equipment._type, equipment.title, equipment.manufacturer, equipment.model, equipment.serial_no,
+            equipment.firmware, equipment.version, equipment.pt2g_version, equipment.br_pdx,
+            equipment.pdu_api, equipment.samdiax_version, equipment.system, equipment.java, equipment.mode,
+        ],
+    )?;
+    Ok(tx.last_insert_rowid())
+}
+
+fn insert_measurements(tx: &Transaction, log_id: i64, section_id: i64, parent_id: Option<i64>, measurements: &[Measurement]) -> Result<()> {
+    for (position, measurement) in measurements.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO measurements (log_id, section_id, parent_id, position, object, title) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![log_id, section_id, parent_id, position, measurement.get_object(), measurement.get_title()],
+        )?;
+        let measurement_id = tx.last_insert_rowid();
+        for (position, value) in measurement.get_values().into_iter().flatten().enumerate() {
+            tx.execute(
+                "INSERT INTO measurement_values (log_id, measurement_id, position, format, label, text, unit, value)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![log_id, measurement_id, position, value.get_format(), value.get_label(), value.get_text(),
+                        value.get_unit(), value.get_value()],
+            )?;
+        }
+        if let Some(submeasurements) = measurement.get_submeasurements() {
+            insert_measurements(tx, log_id, section_id, Some(measurement_id), submeasurements)?;
+        }
+    }
+    Ok(())
+}
+
+/// Appends a log to the database, returning its id, or `None` if a log of the
+/// same vehicle and test run was exported before.
+fn export_log(conn: &mut Connection, val: &VehicleAnalysisLog) -> Result<Option<i64>> {
+    let tx = conn.transaction()?;
+    let existing: Option<i64> = tx.query_row(
+        "SELECT id FROM logs WHERE vin = ?1 AND protocol_type = ?2 AND start_test = ?3 AND end_test = ?4",
+        params![val.results_header.vehicle.ident.vin, val.result.header.protocol_type,
+                val.result.header.start_test.format(TIME_FORMAT).to_string(),
+                val.result.header.end_test.format(TIME_FORMAT).to_string()],
+        |row| row.get(0),
+    ).optional()?;
+    if existing.is_some() {
+        return Ok(None);
+    }
+
+    let log_id = insert_log(&tx, val)?;
+    for (position, section) in val.result.sections.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO sections (log_id, position, object, title) VALUES (?1, ?2, ?3, ?4)",
+            params![log_id, position, section.get_object(), section.get_title()],
+        )?;
+        insert_measurements(&tx, log_id, tx.last_insert_rowid(), None, section.get_measurements())?;
+    }
+    tx.commit()?;
+    Ok(Some(log_id))
+}
+
+pub fn export(args: &ExportArgs) -> Result<()> {
+    let mut conn = Connection::open(&args.sqlite)?;
+    conn.execute_batch(SCHEMA)?;
+
+    for input in &args.inputs {
+        let val = args.load.load(input)?;
+        match export_log(&mut conn, &val)? {
+            Some(log_id) => println!("{} :: exported as log {}", input, log_id),
+            None => println!("{} :: already exported", input),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(xml: &str) -> VehicleAnalysisLog {
+        VehicleAnalysisLog::from_xml_reader(xml.as_bytes()).unwrap()
+    }
+
+    fn count(conn: &Connection, table: &str) -> i64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_export() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        // the schema is created only if missing, so appending to a database works
+        conn.execute_batch(SCHEMA).unwrap();
+        let tables: Vec<String> = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(tables, vec!["logs", "measurement_values", "measurements", "sections"]);
+        let val1 = load(include_str!("../crates/piwis-val/tests/data/FAP_SAMPLE_1.xml"));
+        let val2 = load(include_str!("../crates/piwis-val/tests/data/FAP_SAMPLE_2.xml"));
+
+        let log_id = export_log(&mut conn, &val1).unwrap().unwrap();
+        let (file_name, vin, start_test): (String, String, String) = conn.query_row(
+            "SELECT file_name, vin, start_test FROM logs WHERE id = ?1", [log_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).unwrap();
+        assert_eq!(file_name, "FAP_WP0ZZZ99ZTS392124_20240804_132559_23.0.1");
+        assert_eq!(vin, "WP0ZZZ99ZTS392124");
+        assert_eq!(start_test, "2024-08-04 13:20:00");
+        assert_eq!((count(&conn, "logs"), count(&conn, "sections"), count(&conn, "measurements"), count(&conn, "measurement_values")),
+                   (1, 3, 8, 16));
+
+        let (parent, child): (String, String) = conn.query_row(
+            "SELECT p.title, m.title FROM measurements m JOIN measurements p ON m.parent_id = p.id
+             JOIN sections s ON m.section_id = s.id WHERE s.title = 'Airbag (variant: A2.8)' ORDER BY m.id LIMIT 1", [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!((parent.as_str(), child.as_str()), ("Fault", "erweiterter Fehlerspeicher"));
+        let top_level: i64 = conn.query_row("SELECT COUNT(*) FROM measurements WHERE parent_id IS NULL", [], |row| row.get(0)).unwrap();
+        assert_eq!(top_level, 6);
+
+        // exporting the same log again is a no-op, another test run of the vehicle is added
+        assert_eq!(export_log(&mut conn, &val1).unwrap(), None);
+        assert_eq!(count(&conn, "logs"), 1);
+        assert!(export_log(&mut conn, &val2).unwrap().is_some());
+        assert_eq!(count(&conn, "logs"), 2);
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::Result;
+use piwis_val::identification::{diff_firmware, FirmwareChange, FirmwareLevel};
+use crate::diff::SectionMatchArgs;
+use crate::load::LoadArgs;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum FirmwareFormat {
+    /// One line per changed field, or per control unit found in one log only
+    #[default]
+    Text,
+    /// An array of the control units whose firmware differs
+    Json,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FirmwareArgs {
+    input1: String,
+    input2: String,
+    #[command(flatten)]
+    load: LoadArgs,
+    #[command(flatten)]
+    section_matching: SectionMatchArgs,
+
+    #[clap(long, value_enum, default_value_t)]
+    format: FirmwareFormat,
+}
+
+fn describe(level: &FirmwareLevel) -> String {
+    let fields = [
+        ("SW", &level.software_version),
+        ("SW part number", &level.software_part_number),
+        ("HW", &level.hardware_version),
+    ];
+    let known: Vec<_> = fields.iter()
+        .filter_map(|(name, value)| value.as_ref().map(|v| format!("{} {}", name, v)))
+        .collect();
+    if known.is_empty() {
+        "no identification".to_string()
+    } else {
+        known.join(", ")
+    }
+}
+
+fn print_changed(section: &str, old: &FirmwareLevel, new: &FirmwareLevel) {
+    let fields = [
+        ("software version", &old.software_version, &new.software_version),
+        ("software part number", &old.software_part_number, &new.software_part_number),
+        ("hardware version", &old.hardware_version, &new.hardware_version),
+    ];
+    for (name, old, new) in fields {
+        if old != new {
+            println!("{} // {} :: '{}' -> '{}'", section, name,
+                old.as_deref().unwrap_or("<undefined>"),
+                new.as_deref().unwrap_or("<undefined>"));
+        }
+    }
+}
+
+pub fn firmware(args: &FirmwareArgs) -> Result<()> {
+    let val1 = &args.load.load(&args.input1)?;
+    let val2 = &args.load.load(&args.input2)?;
+
+    let entries = diff_firmware(val1, val2, &args.section_matching.matching());
+    match args.format {
+        FirmwareFormat::Text => {
+            for entry in &entries {
+                match &entry.change {
+                    FirmwareChange::Changed { old, new } => print_changed(&entry.section, old, new),
+                    FirmwareChange::Appeared { new } => println!("{} :: ECU was not found in first VAL ({})", entry.section, describe(new)),
+                    FirmwareChange::Disappeared { old } => println!("{} :: ECU was not found in second VAL ({})", entry.section, describe(old)),
+                }
+            }
+        }
+        FirmwareFormat::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use anyhow::{bail, Result};
+use piwis_val::query::{Query, QueryMatch};
+use serde::Serialize;
+use crate::load::LoadArgs;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum GetFormat {
+    /// One value per line
+    #[default]
+    Text,
+    /// An array of the matching values with their paths
+    Json,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct GetArgs {
+    input: String,
+    /// `section/measurement/.../value`, matching titles and the value's label or text;
+    /// `*` and `?` match within a segment, `**` any number of nested measurements
+    path: Query,
+    #[command(flatten)]
+    load: LoadArgs,
+
+    /// Print the section and measurements leading to each value
+    #[clap(long)]
+    with_path: bool,
+    #[clap(long, value_enum, default_value_t)]
+    format: GetFormat,
+}
+
+#[derive(Serialize, Debug)]
+struct GetRow {
+    section: String,
+    measurements: Vec<String>,
+    label: String,
+    text: String,
+    unit: Option<String>,
+    value: Option<String>,
+}
+
+impl From<&QueryMatch<'_>> for GetRow {
+    fn from(m: &QueryMatch) -> Self {
+        GetRow {
+            section: m.path.section.to_string(),
+            measurements: m.path.measurements.iter().map(|m| m.to_string()).collect(),
+            label: m.value.get_label().clone(),
+            text: m.value.get_text().clone(),
+            unit: m.value.get_unit().cloned(),
+            value: m.value.get_value().cloned(),
+        }
+    }
+}
+
+pub fn get(args: &GetArgs) -> Result<()> {
+    let val = &args.load.load(&args.input)?;
+
+    let rows: Vec<_> = val.query(&args.path).iter().map(GetRow::from).collect();
+    if rows.is_empty() {
+        bail!("no value matches '{}'", args.path);
+    }
+    match args.format {
+        GetFormat::Text => for row in rows {
+            let value = row.value.unwrap_or_default();
+            if args.with_path {
+                let mut p0 = vec![row.section];
+                p0.extend(row.measurements);
+                p0.push(row.text);
+                println!("{}: {}", p0.join(" >> "), value);
+            } else {
+                println!("{}", value);
+            }
+        },
+        GetFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+    }
+
+    Ok(())
+}
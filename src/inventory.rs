@@ -0,0 +1,154 @@
+use std::io::Write;
+use anyhow::Result;
+use piwis_val::{Measurement, Section, VehicleAnalysisLog};
+use serde::Serialize;
+use crate::load::LoadArgs;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum InventoryFormat {
+    #[default]
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct InventoryArgs {
+    input: String,
+    #[command(flatten)]
+    load: LoadArgs,
+
+    #[clap(long, value_enum, default_value_t)]
+    format: InventoryFormat,
+}
+
+#[derive(Serialize, Debug)]
+struct InventoryRow {
+    section: String,
+    part_number: Option<String>,
+    hardware_version: Option<String>,
+    software_version: Option<String>,
+    serial_number: Option<String>,
+    faults: usize,
+    coding_read: bool,
+}
+
+impl From<&Section> for InventoryRow {
+    fn from(section: &Section) -> Self {
+        let identification = section.get_identification().unwrap_or_default();
+        InventoryRow {
+            section: section.get_title().clone(),
+            part_number: identification.part_number,
+            hardware_version: identification.hardware_version,
+            software_version: identification.software_version,
+            serial_number: identification.serial_number,
+            faults: section.get_faults().len(),
+            coding_read: section.get_measurements().iter().any(|m| matches!(m, Measurement::Codierung(_))),
+        }
+    }
+}
+
+const HEADERS: [&str; 7] = ["ECU", "Part number", "HW version", "SW version", "Serial number", "Faults", "Coding read"];
+
+impl InventoryRow {
+    fn columns(&self) -> [String; 7] {
+        [
+            self.section.clone(),
+            self.part_number.clone().unwrap_or_default(),
+            self.hardware_version.clone().unwrap_or_default(),
+            self.software_version.clone().unwrap_or_default(),
+            self.serial_number.clone().unwrap_or_default(),
+            self.faults.to_string(),
+            if self.coding_read { "yes" } else { "no" }.to_string(),
+        ]
+    }
+}
+
+fn print_table(rows: &[InventoryRow]) {
+    let rows: Vec<_> = rows.iter().map(InventoryRow::columns).collect();
+    let mut widths = HEADERS.map(|h| h.chars().count());
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+
+    let print_row = |columns: &[String]| {
+        let line: Vec<_> = columns.iter().zip(widths).map(|(c, w)| format!("{:<w$}", c, w = w)).collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(&HEADERS.map(String::from));
+    print_row(&widths.map(|w| "-".repeat(w)));
+    for row in &rows {
+        print_row(row);
+    }
+}
+
+fn write_csv<W: Write>(writer: W, rows: &[InventoryRow]) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(HEADERS)?;
+    for row in rows {
+        writer.write_record(row.columns())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// One row per control unit; sections of other objects are left out.
+fn rows(val: &VehicleAnalysisLog) -> Vec<InventoryRow> {
+    val.result.sections.iter()
+        .filter(|section| matches!(section, Section::ECU(_)))
+        .map(InventoryRow::from)
+        .collect()
+}
+
+pub fn inventory(args: &InventoryArgs) -> Result<()> {
+    let val = &args.load.load(&args.input)?;
+
+    let rows = rows(val);
+    match args.format {
+        InventoryFormat::Table => print_table(&rows),
+        InventoryFormat::Csv => write_csv(std::io::stdout(), &rows)?,
+        InventoryFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use piwis_val::LoadOptions;
+
+    #[test]
+    fn test_inventory() {
+        let xml = include_str!("../crates/piwis-val/tests/data/FAP_SAMPLE_1.xml")
+            .replace("<TITLE>Gateway (A7.1)</TITLE>", "<TITLE>Gateway, central (A7.1)</TITLE>")
+            .replace("<SECTION OBJECT=\"ECU\">\n      <TITLE>Rear-view camera", "<SECTION OBJECT=\"Bus\">\n      <TITLE>Rear-view camera");
+        let val = LoadOptions::lenient().load_xml_reader(xml.as_bytes()).unwrap();
+        let rows = rows(&val);
+        let sections: Vec<_> = rows.iter().map(|row| row.section.as_str()).collect();
+        assert_eq!(sections, vec!["Gateway, central (A7.1)", "Airbag (variant: A2.8)"]);
+        assert_eq!(rows[0].columns(), ["Gateway, central (A7.1)", "9Y0907530AB", "H05", "0456", "00001234567890", "0", "yes"].map(String::from));
+        assert_eq!((rows[1].faults, rows[1].coding_read), (2, false));
+
+        let mut csv = vec![];
+        write_csv(&mut csv, &rows).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "ECU,Part number,HW version,SW version,Serial number,Faults,Coding read");
+        assert_eq!(lines[1], "\"Gateway, central (A7.1)\",9Y0907530AB,H05,0456,00001234567890,0,yes");
+        assert_eq!(lines.len(), 3);
+
+        let json: serde_json::Value = serde_json::to_value(&rows).unwrap();
+        assert_eq!(json[1], serde_json::json!({
+            "section": "Airbag (variant: A2.8)",
+            "part_number": null,
+            "hardware_version": null,
+            "software_version": null,
+            "serial_number": null,
+            "faults": 2,
+            "coding_read": false,
+        }));
+    }
+}
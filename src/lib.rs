@@ -1,2 +1,9 @@
+pub mod anonymize;
+pub mod archive;
 pub mod diff;
 pub mod dump;
+pub mod export;
+pub mod firmware;
+pub mod get;
+pub mod inventory;
+pub mod load;
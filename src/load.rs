@@ -0,0 +1,96 @@
+use std::cell::Cell;
+use std::io::Read;
+use anyhow::{bail, Result};
+use piwis_val::archive::{ProtocolSelector, ValArchive};
+use piwis_val::matching::SectionFilter;
+use piwis_val::rules::Matcher;
+use piwis_val::{LoadOptions, VehicleAnalysisLog};
+
+// Options shared by every command that reads a VAL. This and the other argument
+// groups flattened into subcommands, `SectionArgs` and `diff::SectionMatchArgs`,
+// have plain comments: a doc comment would become the about text of every
+// subcommand flattening the group.
+#[derive(clap::Args, Debug)]
+pub struct LoadArgs {
+    /// Keep unknown elements and section, measurement or value types, with a
+    /// warning, instead of failing
+    #[clap(long)]
+    lenient: bool,
+    /// Protocol to use from archives holding several, by index or protocol type
+    #[clap(long)]
+    protocol: Option<ProtocolSelector>,
+    #[clap(skip)]
+    stdin_read: Cell<bool>,
+}
+
+impl LoadArgs {
+    /// Loads a VAL zip, a bare FAP XML, an extracted directory or, for `-`,
+    /// either of the former two from stdin.
+    pub fn load(&self, input: &str) -> Result<VehicleAnalysisLog> {
+        let options = self.options();
+        let val = if input == "-" {
+            options.load_bytes(&self.read_stdin()?)?
+        } else {
+            options.load_path(input)?
+        };
+        print_warnings(input, &val);
+        Ok(val)
+    }
+
+    /// Loads every protocol and attachment of a VAL zip or an extracted directory,
+    /// a bare FAP XML as its only protocol, or either of the former two from stdin
+    /// for `-`.
+    pub fn load_archive(&self, input: &str) -> Result<ValArchive> {
+        let options = self.options();
+        let archive = if input == "-" {
+            options.load_archive_bytes(&self.read_stdin()?)?
+        } else {
+            options.load_archive_path(input)?
+        };
+        for protocol in &archive.protocols {
+            print_warnings(&format!("{}/{}", input, protocol.name), &protocol.log);
+        }
+        Ok(archive)
+    }
+
+    pub fn protocol(&self) -> Option<&ProtocolSelector> {
+        self.protocol.as_ref()
+    }
+
+    fn options(&self) -> LoadOptions {
+        LoadOptions { lenient: self.lenient, protocol: self.protocol.clone() }
+    }
+
+    fn read_stdin(&self) -> Result<Vec<u8>> {
+        if self.stdin_read.replace(true) {
+            bail!("stdin can only be read once, pass '-' for a single input");
+        }
+        let mut bytes = vec![];
+        std::io::stdin().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+// Section selection for commands that work on a subset of control units.
+#[derive(clap::Args, Debug)]
+pub struct SectionArgs {
+    /// Only use sections whose title matches, as a glob (Airbag*) or a regex
+    /// between slashes (/^BCM/); may be repeated
+    #[clap(long = "section", value_name = "PATTERN")]
+    include: Vec<Matcher>,
+    /// Skip sections whose title matches; may be repeated
+    #[clap(long = "exclude-section", value_name = "PATTERN")]
+    exclude: Vec<Matcher>,
+}
+
+impl SectionArgs {
+    pub fn filter(&self) -> SectionFilter {
+        SectionFilter { include: self.include.clone(), exclude: self.exclude.clone() }
+    }
+}
+
+fn print_warnings(input: &str, val: &VehicleAnalysisLog) {
+    for warning in &val.warnings {
+        eprintln!("{}: {}", input, warning);
+    }
+}
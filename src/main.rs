@@ -1,7 +1,13 @@
 use clap::Parser;
 use anyhow::Result;
+use valrs::anonymize::{anonymize, AnonymizeArgs};
+use valrs::archive::{archive, ArchiveArgs};
 use valrs::diff::{diff, DiffArgs};
 use valrs::dump::{dump, DumpArgs};
+use valrs::export::{export, ExportArgs};
+use valrs::firmware::{firmware, FirmwareArgs};
+use valrs::get::{get, GetArgs};
+use valrs::inventory::{inventory, InventoryArgs};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -12,15 +18,27 @@ pub struct Cli {
 
 #[derive(clap::Subcommand, Debug)]
 enum Commands {
+    Anonymize(AnonymizeArgs),
+    Archive(ArchiveArgs),
     Diff(DiffArgs),
     Dump(DumpArgs),
+    Export(ExportArgs),
+    Firmware(FirmwareArgs),
+    Get(GetArgs),
+    Inventory(InventoryArgs),
 }
 
  fn main() -> Result<()> {
      let args = Cli::parse();
      match &args.command {
+         Commands::Anonymize(cmd_args) => anonymize(cmd_args)?,
+         Commands::Archive(cmd_args) => archive(cmd_args)?,
          Commands::Diff(cmd_args) => diff(cmd_args)?,
-         Commands::Dump(cmd_args) => dump(cmd_args)?
+         Commands::Dump(cmd_args) => dump(cmd_args)?,
+         Commands::Export(cmd_args) => export(cmd_args)?,
+         Commands::Firmware(cmd_args) => firmware(cmd_args)?,
+         Commands::Get(cmd_args) => get(cmd_args)?,
+         Commands::Inventory(cmd_args) => inventory(cmd_args)?,
      }
      Ok(())
 }